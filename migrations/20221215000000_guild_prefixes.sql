@@ -0,0 +1,5 @@
+CREATE TABLE IF NOT EXISTS guild_prefixes (
+  guild_id BIGINT UNSIGNED NOT NULL,
+  prefix VARCHAR(32) NOT NULL,
+  PRIMARY KEY (guild_id, prefix)
+);
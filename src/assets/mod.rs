@@ -1 +1,2 @@
+#[cfg(feature = "private")]
 pub mod init_sql;
\ No newline at end of file
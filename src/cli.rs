@@ -0,0 +1,136 @@
+use std::time::Duration;
+use clap::{ Args, Parser, Subcommand };
+use crate::{ init::ConfigOptions, logging::{ self, Format }, handle::args::parse_duration };
+
+pub const MIGRATIONS_DIR: &str = "./migrations";
+
+/// Mio Engine, without a subcommand the bot is started
+#[derive(Parser)]
+#[command(name = "mio", version, about)]
+pub struct Cli {
+  #[command(flatten)]
+  pub global: GlobalArgs,
+  #[command(subcommand)]
+  pub command: Option<Command>
+}
+
+#[derive(Args)]
+pub struct GlobalArgs {
+  /// Config file, `MIO_CONFIG` or ./config.toml by default
+  #[arg(long, global = true, value_name = "PATH")]
+  pub config: Option<String>,
+  /// Overlay the config with config.dev.toml
+  #[arg(long, global = true, conflicts_with = "prod")]
+  pub dev: bool,
+  /// Overlay the config with config.prod.toml
+  #[arg(long, global = true)]
+  pub prod: bool,
+  /// Override a key of the config, can be repeated
+  #[arg(long = "set", global = true, value_name = "SECTION.KEY=VALUE", value_parser = parse_override)]
+  pub overrides: Vec<(String, String)>,
+  /// Level and levels per module, `info,StatusLoop=debug`. `MIO_LOG` by default
+  #[arg(long, global = true, value_name = "FILTER", value_parser = parse_filter)]
+  pub log_level: Option<String>,
+  #[arg(long, global = true, value_enum, default_value_t = Format::Auto)]
+  pub log_format: Format,
+  /// Do not print the banner at startup
+  #[arg(long, global = true)]
+  pub no_banner: bool
+}
+
+impl GlobalArgs {
+  pub fn config_options(&self) -> ConfigOptions {
+    let env = if self.dev { Some("dev") } else if self.prod { Some("prod") } else { None };
+    ConfigOptions {
+      path: self.config.clone(),
+      env: env.map(|e| e.to_string()),
+      overrides: self.overrides.clone()
+    }
+  }
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+  /// Start the bot
+  Run,
+  /// Validate the config, every problem is listed
+  CheckConfig,
+  Config {
+    #[command(subcommand)]
+    action: ConfigAction
+  },
+  /// Inspect or edit the archive
+  Archive {
+    #[command(subcommand)]
+    action: ArchiveAction
+  },
+  /// Toggle the maintenance mode, a running instance picks it up within a few seconds
+  Maintenance {
+    #[command(subcommand)]
+    action: MaintenanceAction
+  },
+  Db {
+    #[command(subcommand)]
+    action: DbAction
+  },
+  I18n {
+    #[command(subcommand)]
+    action: I18nAction
+  }
+}
+
+#[derive(Subcommand)]
+pub enum ConfigAction {
+  /// Print the effective config and where each value comes from
+  Show
+}
+
+#[derive(Subcommand)]
+pub enum ArchiveAction {
+  /// Print a value of the archive
+  Get { section: String, key: String },
+  /// Write a value in the archive, parsed as JSON or kept as a string
+  Set { section: String, key: String, value: String }
+}
+
+#[derive(Subcommand)]
+pub enum MaintenanceAction {
+  /// Refuse the commands of everyone but the owners and the whitelist
+  On {
+    /// Estimated duration, `30m` or `1h30m`
+    #[arg(long, value_name = "DURATION", value_parser = parse_eta)]
+    eta: Option<Duration>,
+    reason: Option<String>
+  },
+  Off
+}
+
+#[derive(Subcommand)]
+pub enum DbAction {
+  /// Apply the pending migrations
+  Migrate {
+    #[arg(long, value_name = "DIR", default_value = MIGRATIONS_DIR)]
+    dir: String
+  }
+}
+
+#[derive(Subcommand)]
+pub enum I18nAction {
+  /// Compare every language with the default one
+  Check
+}
+
+fn parse_filter(value: &str) -> Result<String, String> {
+  logging::Filter::parse(value).map(|_| value.to_string())
+}
+
+fn parse_eta(value: &str) -> Result<Duration, String> {
+  parse_duration(value).ok_or("expected a duration such as 30m or 1h30m".to_string())
+}
+
+fn parse_override(value: &str) -> Result<(String, String), String> {
+  match value.split_once('=') {
+    Some((key, value)) if !key.trim().is_empty() => Ok((key.trim().to_string(), value.trim().to_string())),
+    _ => Err("expected section.key=value".to_string())
+  }
+}
@@ -0,0 +1,441 @@
+use std::{fmt::Display, time::Duration};
+use serenity::model::id::UserId;
+
+// ==================================
+// errors
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgumentErrorKind {
+  Missing,
+  MissingValue,
+  NotAnInteger,
+  OutOfRange(i64, i64),
+  InvalidDuration,
+  InvalidUser,
+  /// the value is not one of the accepted ones
+  InvalidChoice(Vec<String>)
+}
+
+/// Describe which argument failed to parse and why.
+/// The message shown to the user is translated from the kind by the command handler
+#[derive(Debug, Clone)]
+pub struct ArgumentError {
+  pub name: String,
+  /// 1-based position of the argument, 0 for flags and options
+  pub position: usize,
+  pub value: Option<String>,
+  pub kind: ArgumentErrorKind
+}
+
+impl ArgumentError {
+  pub fn new(name: &str, position: usize, value: Option<&str>, kind: ArgumentErrorKind) -> Self {
+    ArgumentError { name: name.to_string(), position, value: value.map(|v| v.to_string()), kind }
+  }
+}
+
+impl Display for ArgumentError {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    match &self.value {
+      Some(value) => write!(f, "argument `{}` (position {}, value `{}`): {:?}", self.name, self.position, value, self.kind),
+      None => write!(f, "argument `{}` (position {}): {:?}", self.name, self.position, self.kind)
+    }
+  }
+}
+
+// ==================================
+// tokenizer
+
+#[derive(Debug, Clone)]
+struct Token {
+  value: String,
+  /// byte offsets of the token in the raw arguments, quotes included
+  start: usize,
+  end: usize,
+  quoted: bool,
+  consumed: bool
+}
+
+impl Token {
+  fn is_flag(&self) -> bool {
+    if self.quoted || self.value.len() < 2 || !self.value.starts_with('-') { return false; }
+    // negative numbers are positional values, not flags
+    !self.value[1..].starts_with(|c: char| c.is_ascii_digit())
+  }
+
+  fn flag_name(&self) -> &str {
+    self.value.trim_start_matches('-')
+  }
+}
+
+/// Split the arguments on whitespace, keeping "quoted strings" (with `\"` escapes) as a single token
+fn tokenize(raw: &str) -> Vec<Token> {
+  let mut tokens: Vec<Token> = Vec::new();
+  let mut chars = raw.char_indices().peekable();
+
+  while let Some((index, c)) = chars.next() {
+    if c.is_whitespace() { continue; }
+
+    if c == '"' {
+      let mut value = String::new();
+      // an unterminated quote runs to the end of the line
+      let mut end = raw.len();
+      while let Some((i, c)) = chars.next() {
+        match c {
+          '\\' if matches!(chars.peek(), Some((_, '"'))) => {
+            value.push('"');
+            chars.next();
+          }
+          '"' => {
+            end = i + 1;
+            break;
+          }
+          _ => value.push(c)
+        }
+      }
+      tokens.push(Token { value, start: index, end, quoted: true, consumed: false });
+    } else {
+      let mut value = String::from(c);
+      let mut end = index + c.len_utf8();
+      while let Some((i, c)) = chars.peek() {
+        if c.is_whitespace() { break; }
+        value.push(*c);
+        end = i + c.len_utf8();
+        chars.next();
+      }
+      tokens.push(Token { value, start: index, end, quoted: false, consumed: false });
+    }
+  }
+
+  tokens
+}
+
+// ==================================
+// parser
+
+/// Typed cursor over the arguments of a command.
+///
+/// Flags and options can appear anywhere, so they should be read before the positional arguments.
+#[derive(Debug, Clone)]
+pub struct Args {
+  raw: String,
+  tokens: Vec<Token>,
+  position: usize
+}
+
+impl Args {
+  pub fn new(raw: &str) -> Self {
+    Args { raw: raw.to_string(), tokens: tokenize(raw), position: 0 }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.tokens.iter().all(|t| t.consumed)
+  }
+
+  /// Boolean flag such as `--silent` or `-s`
+  pub fn flag(&mut self, long: &str, short: Option<&str>) -> bool {
+    match self.find_flag(long, short) {
+      Some(index) => {
+        self.tokens[index].consumed = true;
+        true
+      }
+      None => false
+    }
+  }
+
+  /// Option with a value such as `-n 5`, `--number 5` or `--number=5`
+  pub fn option(&mut self, long: &str, short: Option<&str>) -> Result<Option<String>, ArgumentError> {
+    // --long=value
+    let inline = self.tokens.iter().position(|t| {
+      !t.consumed && t.is_flag() && t.value.starts_with("--") && t.flag_name().starts_with(&format!("{long}="))
+    });
+    if let Some(index) = inline {
+      self.tokens[index].consumed = true;
+      return Ok(Some(self.tokens[index].flag_name()[(long.len() + 1)..].to_string()));
+    }
+
+    match self.find_flag(long, short) {
+      Some(index) => {
+        self.tokens[index].consumed = true;
+        match self.tokens.get_mut(index + 1) {
+          Some(next) if !next.consumed && !next.is_flag() => {
+            next.consumed = true;
+            Ok(Some(next.value.clone()))
+          }
+          _ => Err(ArgumentError::new(long, 0, None, ArgumentErrorKind::MissingValue))
+        }
+      }
+      None => Ok(None)
+    }
+  }
+
+  pub fn option_integer(&mut self, long: &str, short: Option<&str>) -> Result<Option<i64>, ArgumentError> {
+    match self.option(long, short)? {
+      Some(value) => value.parse::<i64>()
+        .map(Some)
+        .map_err(|_| ArgumentError::new(long, 0, Some(&value), ArgumentErrorKind::NotAnInteger)),
+      None => Ok(None)
+    }
+  }
+
+  /// Next positional argument as a string
+  pub fn string(&mut self, name: &str) -> Result<String, ArgumentError> {
+    self.next_positional(name).map(|(_, value)| value)
+  }
+
+  pub fn integer(&mut self, name: &str) -> Result<i64, ArgumentError> {
+    let (position, value) = self.next_positional(name)?;
+    value.parse::<i64>().map_err(|_| ArgumentError::new(name, position, Some(&value), ArgumentErrorKind::NotAnInteger))
+  }
+
+  pub fn integer_in(&mut self, name: &str, min: i64, max: i64) -> Result<i64, ArgumentError> {
+    let position = self.position + 1;
+    let value = self.integer(name)?;
+    if value < min || value > max {
+      return Err(ArgumentError::new(name, position, Some(&value.to_string()), ArgumentErrorKind::OutOfRange(min, max)));
+    }
+    Ok(value)
+  }
+
+  pub fn duration(&mut self, name: &str) -> Result<Duration, ArgumentError> {
+    let (position, value) = self.next_positional(name)?;
+    parse_duration(&value).ok_or_else(|| ArgumentError::new(name, position, Some(&value), ArgumentErrorKind::InvalidDuration))
+  }
+
+  pub fn user(&mut self, name: &str) -> Result<UserId, ArgumentError> {
+    let (position, value) = self.next_positional(name)?;
+    parse_mention(&value, &["<@!", "<@"])
+      .map(UserId)
+      .ok_or_else(|| ArgumentError::new(name, position, Some(&value), ArgumentErrorKind::InvalidUser))
+  }
+
+  /// Optional variant of every positional getter: `None` when there is nothing left to read
+  pub fn optional<T>(&mut self, getter: impl FnOnce(&mut Self) -> Result<T, ArgumentError>) -> Result<Option<T>, ArgumentError> {
+    if self.is_empty() { Ok(None) } else { getter(self).map(Some) }
+  }
+
+  /// Every unconsumed token up to the end of the line, as typed by the user.
+  /// The flags and options already read are left out and a quoted token loses its quotes
+  pub fn rest(&mut self, name: &str) -> Result<String, ArgumentError> {
+    self.position += 1;
+    if self.is_empty() {
+      return Err(ArgumentError::new(name, self.position, None, ArgumentErrorKind::Missing));
+    }
+
+    let mut rest = String::new();
+    // end of the previous token when it was kept, the spacing typed in between is kept too
+    let mut previous: Option<usize> = None;
+    for token in self.tokens.iter_mut() {
+      if token.consumed {
+        previous = None;
+        continue;
+      }
+      match previous {
+        Some(end) => rest.push_str(&self.raw[end..token.start]),
+        None if !rest.is_empty() => rest.push(' '),
+        None => {}
+      }
+      if token.quoted { rest.push_str(&token.value) } else { rest.push_str(&self.raw[token.start..token.end]) }
+      token.consumed = true;
+      previous = Some(token.end);
+    }
+    Ok(rest)
+  }
+
+  fn find_flag(&self, long: &str, short: Option<&str>) -> Option<usize> {
+    self.tokens.iter().position(|t| {
+      !t.consumed && t.is_flag() && (
+        (t.value.starts_with("--") && t.flag_name() == long)
+        || (!t.value.starts_with("--") && Some(t.flag_name()) == short)
+      )
+    })
+  }
+
+  fn next_positional(&mut self, name: &str) -> Result<(usize, String), ArgumentError> {
+    self.position += 1;
+    match self.tokens.iter_mut().find(|t| !t.consumed) {
+      Some(token) => {
+        token.consumed = true;
+        Ok((self.position, token.value.clone()))
+      }
+      None => Err(ArgumentError::new(name, self.position, None, ArgumentErrorKind::Missing))
+    }
+  }
+}
+
+/// Parse durations such as `45s`, `10m`, `1h30m`, `2d` or `1w`, a bare number is read as seconds
+pub fn parse_duration(value: &str) -> Option<Duration> {
+  if let Ok(secs) = value.parse::<u64>() { return Some(Duration::from_secs(secs)); }
+
+  let mut total: u64 = 0;
+  let mut number = String::new();
+  for c in value.chars() {
+    if c.is_ascii_digit() {
+      number.push(c);
+      continue;
+    }
+    let amount = number.parse::<u64>().ok()?;
+    let unit = match c.to_ascii_lowercase() {
+      's' => 1,
+      'm' => 60,
+      'h' => 60 * 60,
+      'd' => 60 * 60 * 24,
+      'w' => 60 * 60 * 24 * 7,
+      _ => return None
+    };
+    total = total.checked_add(amount.checked_mul(unit)?)?;
+    number.clear();
+  }
+
+  if !number.is_empty() || total == 0 { None } else { Some(Duration::from_secs(total)) }
+}
+
+/// Accept `<prefix123>` mentions or a raw snowflake
+fn parse_mention(value: &str, prefixes: &[&str]) -> Option<u64> {
+  if let Ok(id) = value.parse::<u64>() { return Some(id); }
+
+  let inner = value.strip_suffix('>')?;
+  prefixes.iter()
+    .find_map(|p| inner.strip_prefix(p))
+    .and_then(|id| id.parse::<u64>().ok())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn values(raw: &str) -> Vec<String> {
+    tokenize(raw).into_iter().map(|t| t.value).collect()
+  }
+
+  #[test]
+  fn tokenize_keeps_quoted_strings() {
+    assert_eq!(values("  a  \"b c\"\td "), vec!["a", "b c", "d"]);
+    assert_eq!(values(r#"say "a \"quoted\" word""#), vec!["say", "a \"quoted\" word"]);
+    assert_eq!(values("\"unterminated quote"), vec!["unterminated quote"]);
+    assert_eq!(values("\"\""), vec![""]);
+    assert!(values("   ").is_empty());
+  }
+
+  #[test]
+  fn tokenize_records_the_spans() {
+    let raw = "é \"b c\" d";
+    let spans: Vec<&str> = tokenize(raw).iter().map(|t| &raw[t.start..t.end]).collect();
+    assert_eq!(spans, vec!["é", "\"b c\"", "d"]);
+  }
+
+  #[test]
+  fn flags_are_found_anywhere() {
+    let mut args = Args::new("a --silent b -v");
+    assert!(args.flag("silent", None));
+    assert!(args.flag("verbose", Some("v")));
+    assert!(!args.flag("silent", None));
+    assert_eq!(args.string("first").unwrap(), "a");
+    assert_eq!(args.string("second").unwrap(), "b");
+    assert!(args.is_empty());
+  }
+
+  #[test]
+  fn quoted_and_negative_values_are_not_flags() {
+    let mut args = Args::new("\"--silent\" -5");
+    assert!(!args.flag("silent", None));
+    assert_eq!(args.string("text").unwrap(), "--silent");
+    assert_eq!(args.integer("number").unwrap(), -5);
+  }
+
+  #[test]
+  fn options_accept_every_form() {
+    let mut args = Args::new("-n 5 --name=mio --count 3 text");
+    assert_eq!(args.option("number", Some("n")).unwrap().as_deref(), Some("5"));
+    assert_eq!(args.option("name", None).unwrap().as_deref(), Some("mio"));
+    assert_eq!(args.option_integer("count", None).unwrap(), Some(3));
+    assert_eq!(args.option("missing", None).unwrap(), None);
+    assert_eq!(args.string("text").unwrap(), "text");
+  }
+
+  #[test]
+  fn option_errors() {
+    let err = Args::new("--number").option("number", None).unwrap_err();
+    assert_eq!(err.kind, ArgumentErrorKind::MissingValue);
+    let err = Args::new("--number --silent").option("number", None).unwrap_err();
+    assert_eq!(err.kind, ArgumentErrorKind::MissingValue);
+    let err = Args::new("--number five").option_integer("number", None).unwrap_err();
+    assert_eq!(err.kind, ArgumentErrorKind::NotAnInteger);
+    assert_eq!(err.value.as_deref(), Some("five"));
+  }
+
+  #[test]
+  fn positional_errors_report_the_position() {
+    let mut args = Args::new("1 deux");
+    assert_eq!(args.integer("first").unwrap(), 1);
+    let err = args.integer("second").unwrap_err();
+    assert_eq!((err.position, err.kind), (2, ArgumentErrorKind::NotAnInteger));
+    let err = args.string("third").unwrap_err();
+    assert_eq!((err.position, err.kind), (3, ArgumentErrorKind::Missing));
+
+    let err = Args::new("12").integer_in("n", 1, 10).unwrap_err();
+    assert_eq!(err.to_string(), "argument `n` (position 1, value `12`): OutOfRange(1, 10)");
+    assert_eq!((err.position, err.kind), (1, ArgumentErrorKind::OutOfRange(1, 10)));
+  }
+
+  #[test]
+  fn optional_is_none_when_nothing_is_left() {
+    let mut args = Args::new("--silent");
+    assert!(args.flag("silent", None));
+    assert_eq!(args.optional(|a| a.string("text")).unwrap(), None);
+    assert_eq!(Args::new("x").optional(|a| a.string("text")).unwrap().as_deref(), Some("x"));
+  }
+
+  #[test]
+  fn rest_keeps_the_typed_spacing() {
+    let mut args = Args::new("add  hello   world\nagain");
+    assert_eq!(args.string("action").unwrap(), "add");
+    assert_eq!(args.rest("text").unwrap(), "hello   world\nagain");
+    assert!(args.is_empty());
+    assert_eq!(args.rest("text").unwrap_err().kind, ArgumentErrorKind::Missing);
+  }
+
+  #[test]
+  fn rest_skips_the_consumed_options() {
+    let mut args = Args::new("hello --weight 2 world -s");
+    assert_eq!(args.option_integer("weight", None).unwrap(), Some(2));
+    assert!(args.flag("silent", Some("s")));
+    assert_eq!(args.rest("text").unwrap(), "hello world");
+
+    let mut args = Args::new("hello --weight 2");
+    assert_eq!(args.option_integer("weight", None).unwrap(), Some(2));
+    assert_eq!(args.rest("text").unwrap(), "hello");
+  }
+
+  #[test]
+  fn rest_unquotes_the_quoted_tokens() {
+    let mut args = Args::new("\"hello world\" and more");
+    assert_eq!(args.rest("text").unwrap(), "hello world and more");
+  }
+
+  #[test]
+  fn durations() {
+    assert_eq!(parse_duration("45"), Some(Duration::from_secs(45)));
+    assert_eq!(parse_duration("45s"), Some(Duration::from_secs(45)));
+    assert_eq!(parse_duration("10m"), Some(Duration::from_secs(600)));
+    assert_eq!(parse_duration("1h30m"), Some(Duration::from_secs(5400)));
+    assert_eq!(parse_duration("2D"), Some(Duration::from_secs(172800)));
+    assert_eq!(parse_duration("1w"), Some(Duration::from_secs(604800)));
+    for invalid in ["", "m", "10x", "1h30", "0m", "-5m", "99999999999999999999w"] {
+      assert_eq!(parse_duration(invalid), None, "{invalid}");
+    }
+  }
+
+  #[test]
+  fn mentions_and_ids() {
+    assert_eq!(parse_mention("<@123>", &["<@!", "<@"]), Some(123));
+    assert_eq!(parse_mention("<@!123>", &["<@!", "<@"]), Some(123));
+    assert_eq!(parse_mention("123", &["<#"]), Some(123));
+    assert_eq!(parse_mention("<#123>", &["<@"]), None);
+    assert_eq!(parse_mention("<@123", &["<@"]), None);
+    assert_eq!(parse_mention("<@abc>", &["<@"]), None);
+
+    let mut args = Args::new("<@1> <@&4>");
+    assert_eq!(args.user("user").unwrap(), UserId(1));
+    assert_eq!(args.user("user").unwrap_err().kind, ArgumentErrorKind::InvalidUser);
+  }
+}
@@ -0,0 +1,116 @@
+use serenity::{ model::{ Permissions, id::{ RoleId, UserId } }, prelude::Context };
+
+use crate::{ Storage, libs::i18n };
+use super::{ commands::CommandInfo, source::CommandSource };
+
+#[derive(Debug, Clone)]
+pub enum CheckFailure {
+  OwnerOnly,
+  GuildOnly,
+  DmOnly,
+  /// permissions missing to the member who used the command
+  MissingPermissions(Permissions),
+  /// permissions missing to the bot itself
+  BotMissingPermissions(Permissions),
+  /// the permissions could not be fetched from Discord
+  Unavailable(String)
+}
+
+impl CheckFailure {
+  pub fn as_str(&self) -> &str {
+    match self {
+      CheckFailure::OwnerOnly => "OwnerOnly",
+      CheckFailure::GuildOnly => "GuildOnly",
+      CheckFailure::DmOnly => "DmOnly",
+      CheckFailure::MissingPermissions(_) => "MissingPermissions",
+      CheckFailure::BotMissingPermissions(_) => "BotMissingPermissions",
+      CheckFailure::Unavailable(_) => "Unavailable"
+    }
+  }
+
+  /// Explanation sent to the user, in `lang`
+  pub async fn describe(&self, lang: &str) -> String {
+    match self {
+      CheckFailure::OwnerOnly => i18n::translate(lang, "errors.owner_only", &[]).await,
+      CheckFailure::GuildOnly => i18n::translate(lang, "errors.guild_only", &[]).await,
+      CheckFailure::DmOnly => i18n::translate(lang, "errors.dm_only", &[]).await,
+      CheckFailure::MissingPermissions(missing) => {
+        i18n::translate(lang, "errors.missing_permissions", &[("permissions", permission_names(*missing))]).await
+      }
+      CheckFailure::BotMissingPermissions(missing) => {
+        i18n::translate(lang, "errors.bot_missing_permissions", &[("permissions", permission_names(*missing))]).await
+      }
+      CheckFailure::Unavailable(_) => i18n::translate(lang, "errors.permissions_unavailable", &[]).await
+    }
+  }
+}
+
+/// `Manage Guild`, `Send Messages`, ...
+pub fn permission_names(permissions: Permissions) -> String {
+  permissions.get_permission_names()
+    .iter()
+    .map(|p| format!("`{p}`"))
+    .collect::<Vec<String>>()
+    .join(", ")
+}
+
+pub fn is_owner(storage: &Storage, user_id: UserId) -> bool {
+  storage.client.owners.contains(&user_id)
+}
+
+/// Permissions of `user_id` in the channel of the command, `None` in DMs
+pub async fn permissions_in_channel(ctx: &Context, source: &CommandSource<'_>, user_id: UserId) -> Result<Option<Permissions>, CheckFailure> {
+  let guild_id = match source.guild_id() {
+    Some(id) => id,
+    None => return Ok(None)
+  };
+
+  let guild = guild_id.to_partial_guild(ctx).await.map_err(|e| CheckFailure::Unavailable(e.to_string()))?;
+  if guild.owner_id == user_id { return Ok(Some(Permissions::all())) }
+
+  let member = guild_id.member(ctx, user_id).await.map_err(|e| CheckFailure::Unavailable(e.to_string()))?;
+  let channel = source.channel_id().to_channel(ctx).await
+    .map_err(|e| CheckFailure::Unavailable(e.to_string()))?
+    .guild();
+
+  match channel {
+    Some(channel) => guild.user_permissions_in(&channel, &member)
+      .map(Some)
+      .map_err(|e| CheckFailure::Unavailable(e.to_string())),
+    None => {
+      // not a guild channel, the permissions of the roles in the guild are used
+      let everyone = guild.roles.get(&RoleId(guild_id.0)).map(|r| r.permissions).unwrap_or_else(Permissions::empty);
+      let permissions = member.roles.iter().filter_map(|id| guild.roles.get(id)).fold(everyone, |p, role| p | role.permissions);
+      Ok(Some(if permissions.administrator() { Permissions::all() } else { permissions }))
+    }
+  }
+}
+
+/// Check every constraint declared by the command before it runs.
+///
+/// Owners bypass the member permissions, but not the context or the bot permissions.
+pub async fn check(ctx: &Context, source: &CommandSource<'_>, storage: &Storage, info: &CommandInfo) -> Result<(), CheckFailure> {
+  let owner = is_owner(storage, source.author().id);
+
+  if info.owner_only && !owner { return Err(CheckFailure::OwnerOnly) }
+  if info.guild_only && source.guild_id().is_none() { return Err(CheckFailure::GuildOnly) }
+  if info.dm_only && source.guild_id().is_some() { return Err(CheckFailure::DmOnly) }
+
+  if !info.permissions.is_empty() && !owner {
+    if let Some(permissions) = permissions_in_channel(ctx, source, source.author().id).await? {
+      let missing = info.permissions - permissions;
+      if !missing.is_empty() { return Err(CheckFailure::MissingPermissions(missing)) }
+    }
+  }
+
+  if !info.bot_permissions.is_empty() {
+    if let Some(bot_id) = storage.client.id {
+      if let Some(permissions) = permissions_in_channel(ctx, source, bot_id).await? {
+        let missing = info.bot_permissions - permissions;
+        if !missing.is_empty() { return Err(CheckFailure::BotMissingPermissions(missing)) }
+      }
+    }
+  }
+
+  Ok(())
+}
\ No newline at end of file
@@ -0,0 +1,155 @@
+use serenity::{ async_trait, builder::CreateEmbed, prelude::Context };
+use crate::{ Storage, libs::i18n };
+use crate::handle::{ checks, cooldowns::{ self, Cooldown }, source::CommandSource };
+use super::{
+  Command, CommandCategory, CommandData, CommandError, CommandInfo, CommandOption, OptionKind,
+  SlashScope, RegisteredCommand, REGISTRY
+};
+
+/// Number of commands listed on a page of the help
+const PAGE_SIZE: usize = 10;
+const EMBED_COLOR: u32 = 0xC77DFF;
+
+const CATEGORIES: [CommandCategory; 3] = [CommandCategory::Information, CommandCategory::Utility, CommandCategory::Owner];
+
+pub struct Help;
+
+#[async_trait]
+impl Command for Help {
+  fn info(&self) -> CommandInfo {
+    CommandInfo {
+      name: "help",
+      aliases: &["h", "aide", "commands"],
+      description: "Liste les commandes ou affiche le détail d'une commande",
+      usage: "help [commande | page]",
+      category: CommandCategory::Information,
+      cooldowns: vec![Cooldown::user(3).with_burst(3)],
+      options: vec![
+        CommandOption::new("commande", "Commande ou numéro de page", OptionKind::String, false)
+      ],
+      slash: SlashScope::Global,
+      ..Default::default()
+    }
+  }
+
+  async fn execute(
+    &self,
+    ctx: &Context,
+    source: &CommandSource<'_>,
+    storage: &Storage,
+    command: &CommandData
+  ) -> Result<(), CommandError> {
+    let lang = source.lang();
+    let mut args = command.parse_args();
+    let query = args.optional(|a| a.string("commande"))?;
+
+    let usable = usable_commands(ctx, source, storage).await;
+
+    let embed = match query {
+      Some(query) if query.parse::<usize>().is_err() => {
+        let found = REGISTRY.find(&query, true).filter(|c| usable.iter().any(|u| u.info.name == c.info.name));
+        match found {
+          Some(registered) => command_embed(&lang, &command.prefix, &registered.info).await,
+          None => {
+            let content = i18n::translate(&lang, "help.not_found", &[("command", query)]).await;
+            source.reply_ephemeral(ctx, content).await?;
+            return Err(CommandError::TreatedException)
+          }
+        }
+      }
+      page => {
+        let page = page.and_then(|p| p.parse::<usize>().ok()).unwrap_or(1);
+        list_embed(&lang, &command.prefix, &usable, page).await
+      }
+    };
+
+    source.reply_embed(ctx, embed).await
+  }
+}
+
+/// Commands the user is allowed to run here: owner-only, context and permission constraints are applied.
+/// The commands gated by a permission are hidden in DMs, where there is nothing to grant it
+async fn usable_commands(ctx: &Context, source: &CommandSource<'_>, storage: &Storage) -> Vec<&'static RegisteredCommand> {
+  let owner = checks::is_owner(storage, source.author().id);
+  let in_guild = source.guild_id().is_some();
+
+  let needs_permissions = !owner && REGISTRY.list().iter().any(|c| !c.info.permissions.is_empty());
+  let permissions = if needs_permissions {
+    checks::permissions_in_channel(ctx, source, source.author().id).await.ok().flatten()
+  } else { None };
+
+  REGISTRY.list().iter()
+    .filter(|c| owner || !c.info.owner_only)
+    .filter(|c| if in_guild { !c.info.dm_only } else { !c.info.guild_only && c.info.permissions.is_empty() })
+    .filter(|c| {
+      owner || c.info.permissions.is_empty()
+        || permissions.map(|p| p.contains(c.info.permissions)).unwrap_or(false)
+    })
+    .collect()
+}
+
+/// Description of the command in `lang`, the one of [CommandInfo] otherwise
+async fn description(lang: &str, info: &CommandInfo) -> String {
+  i18n::try_translate(lang, format!("commands.{}.description", info.name).as_str(), &[]).await
+    .unwrap_or(info.description.to_string())
+}
+
+async fn list_embed(lang: &str, prefix: &str, commands: &[&RegisteredCommand], page: usize) -> CreateEmbed {
+  let mut sorted = commands.to_vec();
+  sorted.sort_by_key(|c| (CATEGORIES.iter().position(|cat| *cat == c.info.category), c.info.name));
+
+  let pages = sorted.len().div_ceil(PAGE_SIZE).max(1);
+  let page = page.clamp(1, pages);
+  let shown = sorted.iter().skip((page - 1) * PAGE_SIZE).take(PAGE_SIZE).collect::<Vec<_>>();
+
+  let mut embed = CreateEmbed::default();
+  embed.color(EMBED_COLOR)
+    .title(i18n::translate(lang, "help.title", &[]).await)
+    .description(i18n::translate(lang, "help.description", &[("prefix", prefix.to_string())]).await)
+    .footer(|f| f.text(format!("Page {page}/{pages}")));
+
+  for category in CATEGORIES.iter() {
+    let mut lines: Vec<String> = Vec::new();
+    for registered in shown.iter().filter(|c| c.info.category == *category) {
+      lines.push(format!("`{prefix}{}` — {}", registered.info.name, description(lang, &registered.info).await));
+    }
+    if !lines.is_empty() {
+      let name = i18n::translate(lang, format!("help.categories.{}", category.as_str()).as_str(), &[]).await;
+      embed.field(name, lines.join("\n"), false);
+    }
+  }
+
+  embed
+}
+
+async fn command_embed(lang: &str, prefix: &str, info: &CommandInfo) -> CreateEmbed {
+  let mut embed = CreateEmbed::default();
+  embed.color(EMBED_COLOR)
+    .title(format!("{prefix}{}", info.name))
+    .description(description(lang, info).await)
+    .field(i18n::translate(lang, "help.usage", &[]).await, format!("`{prefix}{}`", super::usage(lang, info).await), false);
+
+  if !info.aliases.is_empty() {
+    let aliases = info.aliases.iter().map(|a| format!("`{a}`")).collect::<Vec<String>>().join(", ");
+    embed.field(i18n::translate(lang, "help.aliases", &[]).await, aliases, true);
+  }
+
+  if !info.cooldowns.is_empty() {
+    let mut lines: Vec<String> = Vec::new();
+    for cooldown in info.cooldowns.iter() {
+      let bucket = i18n::translate(lang, format!("help.buckets.{}", cooldown.bucket.as_str()).as_str(), &[]).await;
+      lines.push(format!("{} × {} ({bucket})", cooldown.burst, cooldowns::format_remaining(cooldown.duration)));
+    }
+    embed.field(i18n::translate(lang, "help.cooldown", &[]).await, lines.join("\n"), true);
+  }
+
+  if !info.permissions.is_empty() {
+    embed.field(i18n::translate(lang, "help.permissions", &[]).await, checks::permission_names(info.permissions), false);
+  }
+
+  if !info.bot_permissions.is_empty() {
+    embed.field(i18n::translate(lang, "help.bot_permissions", &[]).await, checks::permission_names(info.bot_permissions), false);
+  }
+
+  embed
+}
\ No newline at end of file
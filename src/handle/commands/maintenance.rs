@@ -0,0 +1,135 @@
+use chrono::Utc;
+use serenity::{ async_trait, prelude::Context };
+use crate::{ Storage, utils, maintenance::Maintenance, libs::i18n };
+use crate::handle::{ args::{ parse_duration, ArgumentError, ArgumentErrorKind }, source::CommandSource };
+use super::{Command, CommandCategory, CommandData, CommandError, CommandInfo, REGISTRY};
+
+pub struct MaintenanceCommand;
+
+#[async_trait]
+impl Command for MaintenanceCommand {
+  fn info(&self) -> CommandInfo {
+    CommandInfo {
+      name: "maintenance",
+      aliases: &["mnt"],
+      description: "Active, désactive ou configure le mode maintenance",
+      usage: "maintenance [on [--eta <durée>] [raison] | off | allow <command|user|guild> <valeur> | deny <command|user|guild> <valeur>]",
+      category: CommandCategory::Owner,
+      owner_only: true,
+      ..Default::default()
+    }
+  }
+
+  async fn execute(
+    &self,
+    ctx: &Context,
+    source: &CommandSource<'_>,
+    _storage: &Storage,
+    command: &CommandData
+  ) -> Result<(), CommandError> {
+    let maintenance_lock = {
+      let data = ctx.data.read().await;
+      data.get::<Maintenance>().expect("Expected Maintenance in TypeMap.").clone()
+    };
+
+    let lang = source.lang();
+    let usage = format!("{}{}", command.prefix, super::usage(&lang, &self.info()).await);
+    let mut args = command.parse_args();
+    let eta = args.option("eta", Some("e"))?;
+    let action = args.optional(|a| a.string("action"))?.map(|a| a.to_lowercase());
+
+    let mut maintenance = maintenance_lock.write().await;
+    let content = match action.as_deref() {
+      None | Some("status") => {
+        let content = describe(&lang, &maintenance).await;
+        drop(maintenance);
+        source.reply(ctx, content).await?;
+        return Ok(())
+      }
+      Some("on") => {
+        let eta = match eta {
+          Some(value) => match parse_duration(&value) {
+            Some(duration) => Some(Utc::now().timestamp() + duration.as_secs() as i64),
+            None => return Err(ArgumentError::new("eta", 0, Some(&value), ArgumentErrorKind::InvalidDuration).into())
+          },
+          None => None
+        };
+        let reason = args.optional(|a| a.rest("raison"))?;
+        maintenance.enable(reason, eta);
+        utils::warn("Maintenance", format!("Maintenance mode enabled by {}", source.author().tag()).as_str());
+        i18n::translate(&lang, "maintenance.enabled", &[]).await
+      }
+      Some("off") => {
+        maintenance.disable();
+        utils::info("Maintenance", format!("Maintenance mode disabled by {}", source.author().tag()).as_str());
+        i18n::translate(&lang, "maintenance.disabled", &[]).await
+      }
+      Some(action @ ("allow" | "deny")) => {
+        let allow = action == "allow";
+        let target = args.string("cible")?.to_lowercase();
+        match target.as_str() {
+          "command" | "cmd" => {
+            let name = args.string("commande")?.to_lowercase();
+            let name = REGISTRY.find(&name, true).map(|c| c.info.name.to_string()).unwrap_or(name);
+            toggle(&mut maintenance.commands, name, allow);
+          }
+          "user" => {
+            let user = args.user("utilisateur")?;
+            toggle(&mut maintenance.users, user.0, allow);
+          }
+          "guild" | "server" => {
+            let guild = args.integer("serveur")?;
+            toggle(&mut maintenance.guilds, guild as u64, allow);
+          }
+          _ => {
+            drop(maintenance);
+            source.reply(ctx, i18n::translate(&lang, "maintenance.unknown_target", &[("usage", usage)]).await).await?;
+            return Err(CommandError::TreatedException)
+          }
+        }
+        format!("{}\n{}", i18n::translate(&lang, "maintenance.whitelist_updated", &[]).await, describe(&lang, &maintenance).await)
+      }
+      Some(_) => {
+        drop(maintenance);
+        source.reply(ctx, i18n::translate(&lang, "maintenance.unknown_action", &[("usage", usage)]).await).await?;
+        return Err(CommandError::TreatedException)
+      }
+    };
+
+    if let Err(err) = maintenance.save() {
+      utils::error("Maintenance", "cannot save the maintenance state", err.as_str());
+    }
+    drop(maintenance);
+
+    source.reply(ctx, content).await
+  }
+}
+
+fn toggle<T: PartialEq>(list: &mut Vec<T>, value: T, allow: bool) {
+  let present = list.contains(&value);
+  if allow && !present { list.push(value) }
+  else if !allow && present { list.retain(|v| *v != value) }
+}
+
+async fn describe(lang: &str, maintenance: &Maintenance) -> String {
+  let state = i18n::translate(lang, if maintenance.enabled { "maintenance.status.on" } else { "maintenance.status.off" }, &[]).await;
+  let mut lines = vec![i18n::translate(lang, "maintenance.status.state", &[("state", state)]).await];
+  if let Some(reason) = &maintenance.reason {
+    lines.push(i18n::translate(lang, "maintenance.status.reason", &[("reason", reason.clone())]).await)
+  }
+  if let Some(eta) = maintenance.eta {
+    lines.push(i18n::translate(lang, "maintenance.status.eta", &[("timestamp", eta.to_string())]).await)
+  }
+
+  let none = i18n::translate(lang, "maintenance.status.none", &[]).await;
+  let list = |items: Vec<String>| if items.is_empty() { none.clone() } else { items.join(", ") };
+  let whitelists = [
+    ("maintenance.status.commands", list(maintenance.commands.iter().map(|c| format!("`{c}`")).collect())),
+    ("maintenance.status.users", list(maintenance.users.iter().map(|u| format!("`{u}`")).collect())),
+    ("maintenance.status.guilds", list(maintenance.guilds.iter().map(|g| format!("`{g}`")).collect()))
+  ];
+  for (key, value) in whitelists {
+    lines.push(i18n::translate(lang, key, &[("list", value)]).await);
+  }
+  lines.join("\n")
+}
\ No newline at end of file
@@ -1,99 +1,234 @@
-use std::fmt::Display;
+use std::{fmt::Display, collections::HashMap, time::Instant};
 
-use serenity::{model::prelude::Message, http::CacheHttp, prelude::Context};
-use crate::{ Storage, utils };
+use once_cell::sync::Lazy;
+use serenity::{async_trait, model::{prelude::Message, Permissions}, prelude::Context};
+use crate::{ Storage, utils, shutdown, libs::i18n };
+use super::{
+  args::{ Args, ArgumentError, ArgumentErrorKind },
+  prefixes::{ self, GuildPrefixes },
+  cooldowns::{ self, Cooldown, Cooldowns },
+  checks::{ self, CheckFailure },
+  source::CommandSource,
+  hooks::{ self, HookContext }
+};
 
 // ==================================
 // handler
 
 #[derive(Debug)]
-#[allow(dead_code)]
-pub (in crate::handle) struct CommandData {
+pub struct CommandData {
   pub name: String,
   pub prefix: String,
-  pub args: Vec<String>
+  /// arguments as typed by the user, used by [Args] for typed parsing
+  pub raw_args: String
 }
 
 impl CommandData {
-  fn new(prefix: &String, content: &String) -> Result<CommandData, ()> {
-    let splitted = content[(prefix.len())..].split(" ")
-      .filter(|p| p.trim().len() > 0)
-      .map(|w| w.trim().to_string())
-      .collect::<Vec<String>>();
+  fn new(prefix: &str, content: &str) -> Result<CommandData, ()> {
+    let body = content[(prefix.len())..].trim_start();
+    let name = body.split_whitespace().next().ok_or(())?;
+    let raw_args = body[(name.len())..].trim().to_string();
 
-    if splitted.len() > 0 {
-      Ok(CommandData { name: splitted[0].clone(), prefix: prefix.clone(), args: splitted[1..].to_vec() })
-    } else {
-      Err(())
-    }
+    Ok(CommandData {
+      name: name.to_string(),
+      prefix: prefix.to_string(),
+      raw_args
+    })
   }
-}
 
-fn after_execution(message: &Message, storage: &Storage, cmd: &CommandData){
-  if storage.handler_state.is_dev() {
-    utils::send(
-      "CommandHandler",
-      format!("Command \x1b[33m{n}\x1b[0m used by \x1b[35m{a}\x1b[0m", n = cmd.name, a = message.author.tag()).as_str(),
-      36
-    );
+  /// Typed parser over the arguments of the command
+  pub fn parse_args(&self) -> Args {
+    Args::new(&self.raw_args)
   }
 }
 
 pub async fn execute(
   ctx: &Context,
-  http: &impl CacheHttp,
   message: &Message,
   storage: &Storage
 ) {
-  if message.content.trim().len() < 1 || message.author.bot || !message.content.starts_with(&storage.client.prefix) { return; }
+  if message.content.trim().is_empty() || message.author.bot { return; }
 
-  match CommandData::new(&storage.client.prefix, &message.content) {
+  let prefixes_lock = {
+    let data = ctx.data.read().await;
+    data.get::<GuildPrefixes>().expect("Expected GuildPrefixes in TypeMap.").clone()
+  };
+  let prefix = {
+    let prefixes = prefixes_lock.read().await;
+    let guild_prefixes = message.guild_id.and_then(|id| prefixes.get(id));
+    match prefixes::resolve(message, guild_prefixes, &storage.client.prefix, storage.client.id) {
+      Some(prefix) => prefix,
+      None => return
+    }
+  };
+
+  match CommandData::new(&prefix, &message.content) {
     Ok(cmd) => async {
-      exec_command(ctx, http, message, storage, cmd).await;
+      exec_command(ctx, &CommandSource::from_message(message), storage, cmd).await;
     },
     // Cannot found any command after the prefix
     Err(_) => { return; }
   }.await;
 }
 
-async fn exec_command(
+/// Run a command coming from a message or an interaction, with every check of the framework
+pub (in crate::handle) async fn exec_command(
   ctx: &Context,
-  http: &impl CacheHttp,
-  message: &Message,
+  source: &CommandSource<'_>,
   storage: &Storage,
   command: CommandData
 ){
-  let cmd_result: Result<Result<(), CommandError>, CommandError> = match &command.name.as_str() {
-    &"ping" => {
-      Ok(ping::execute(ctx, http, message, storage, &command).await)
+  // refused once the shutdown started, the running ones are awaited
+  let _in_flight = match shutdown::InFlight::start() {
+    Some(guard) => guard,
+    None => return
+  };
+  let registered = REGISTRY.find(&command.name, storage.client.case_insensitive);
+  let cmd_result: Result<Result<(), CommandError>, CommandError> = match registered {
+    Some(registered) => {
+      let hook = HookContext { ctx, source, storage, command: &command, info: &registered.info };
+      let start = Instant::now();
+
+      let result: Result<(), CommandError> = async {
+        // the permissions come first so owner-only commands stay hidden, even during a maintenance
+        check_permissions(ctx, source, storage, &registered.info).await?;
+        hooks::run_before(&hook).await?;
+        check_cooldowns(ctx, source, &registered.info).await?;
+        registered.command.execute(ctx, source, storage, &command).await
+      }.await;
+
+      hooks::run_after(&hook, start.elapsed(), &result).await;
+      Ok(result)
     },
-    _ => Err(CommandError::CommandNotFound)
+    None => Err(CommandError::CommandNotFound)
   };
 
   match cmd_result {
     Ok(result) => {
       if let Err(err) = result {
-        match err {
+        match &err {
           CommandError::TooEarly | CommandError::CommandNotFound => {
             utils::warn_with_cause("CommandHandler", "An error occured while executing the command", err.as_str())
           }
-          CommandError::TreatedException => {}
+          CommandError::InvalidArgument(arg_err) => {
+            let usage = match registered {
+              Some(registered) => usage(&source.lang(), &registered.info).await,
+              None => String::new()
+            };
+            invalid_argument(ctx, source, &command, &usage, arg_err).await;
+            utils::warn_with_cause("CommandHandler", "Invalid argument given to the command", arg_err.to_string().as_str())
+          }
+          CommandError::TreatedException | CommandError::Cooldown | CommandError::NoPermissions | CommandError::Maintenance => {}
           _ => {
             utils::error("CommandHandler", "An error occured while executing the command", err.as_str())
           }
         }
-      } else {
-        after_execution(message, storage, &command);
       }
     },
     Err(err) => {
+      if let CommandError::CommandNotFound = err {
+        command_not_found(ctx, source, storage, &command).await;
+      }
       utils::warn_with_cause("CommandHandler", "An error occured while executing the command", err.as_str())
     }
   }
 }
 
+/// Run the checks declared by the command and explain to the user what is missing
+async fn check_permissions(ctx: &Context, source: &CommandSource<'_>, storage: &Storage, info: &CommandInfo) -> Result<(), CommandError> {
+  match checks::check(ctx, source, storage, info).await {
+    Ok(()) => Ok(()),
+    Err(failure) => {
+      if let CheckFailure::Unavailable(why) = &failure {
+        utils::warn_with_cause("CommandHandler", "Cannot fetch the permissions", why.as_str());
+      }
+      // owner-only commands stay hidden to everyone else, except on interactions which need an answer
+      if !matches!(failure, CheckFailure::OwnerOnly) || source.is_interaction() {
+        let _ = source.reply_ephemeral(ctx, failure.describe(&source.lang()).await).await;
+      }
+      if storage.handler_state.is_dev() {
+        utils::warn_with_cause("CommandHandler", format!("Command {} refused to {}", info.name, source.author().tag()).as_str(), failure.as_str());
+      }
+      Err(CommandError::NoPermissions)
+    }
+  }
+}
+
+/// Refuse the command while one of its cooldowns is running, the user is told once per cooldown
+async fn check_cooldowns(ctx: &Context, source: &CommandSource<'_>, info: &CommandInfo) -> Result<(), CommandError> {
+  let cooldowns_lock = {
+    let data = ctx.data.read().await;
+    data.get::<Cooldowns>().expect("Expected Cooldowns in TypeMap.").clone()
+  };
+  let result = cooldowns_lock.lock().await.check(info.name, &info.cooldowns, source);
+
+  match result {
+    Ok(()) => Ok(()),
+    Err(hit) => {
+      // an interaction must always be answered
+      if hit.notify || source.is_interaction() {
+        let content = i18n::translate(&source.lang(), "errors.cooldown", &[
+          ("command", info.name.to_string()),
+          ("time", cooldowns::format_remaining(hit.remaining))
+        ]).await;
+        let _ = source.reply_ephemeral(ctx, content).await;
+      }
+      Err(CommandError::Cooldown)
+    }
+  }
+}
+
+/// Suggest the closest registered command, stays silent when nothing is close enough
+async fn command_not_found(ctx: &Context, source: &CommandSource<'_>, storage: &Storage, command: &CommandData) {
+  let name = if storage.client.case_insensitive { command.name.to_lowercase() } else { command.name.clone() };
+  if let Some(suggestion) = REGISTRY.suggest(&name) {
+    let content = i18n::translate(&source.lang(), "errors.command_not_found", &[
+      ("command", command.name.clone()),
+      ("suggestion", format!("{}{}", command.prefix, suggestion))
+    ]).await;
+    let _ = source.reply(ctx, content).await;
+  }
+}
+
+async fn invalid_argument(ctx: &Context, source: &CommandSource<'_>, command: &CommandData, usage: &str, err: &ArgumentError) {
+  let lang = source.lang();
+  let content = i18n::translate(&lang, "errors.invalid_argument", &[
+    ("name", err.name.clone()),
+    ("reason", argument_reason(&lang, &err.kind).await),
+    ("usage", format!("{}{}", command.prefix, usage))
+  ]).await;
+  let _ = source.reply_ephemeral(ctx, content).await;
+}
+
+/// Usage of the command in `lang`, the one of [CommandInfo] otherwise
+pub async fn usage(lang: &str, info: &CommandInfo) -> String {
+  i18n::try_translate(lang, format!("commands.{}.usage", info.name).as_str(), &[]).await
+    .unwrap_or(info.usage.to_string())
+}
+
+/// Why the argument was refused, in the language of the user. The logs use the kind itself
+async fn argument_reason(lang: &str, kind: &ArgumentErrorKind) -> String {
+  let (key, args) = match kind {
+    ArgumentErrorKind::Missing => ("missing", vec![]),
+    ArgumentErrorKind::MissingValue => ("missing_value", vec![]),
+    ArgumentErrorKind::NotAnInteger => ("not_an_integer", vec![]),
+    ArgumentErrorKind::OutOfRange(min, max) => ("out_of_range", vec![("min", min.to_string()), ("max", max.to_string())]),
+    ArgumentErrorKind::InvalidDuration => ("invalid_duration", vec![]),
+    ArgumentErrorKind::InvalidUser => ("invalid_user", vec![]),
+    ArgumentErrorKind::InvalidChoice(choices) => {
+      ("invalid_choice", vec![("choices", choices.iter().map(|c| format!("`{c}`")).collect::<Vec<String>>().join(", "))])
+    }
+  };
+  i18n::translate(lang, &format!("errors.arguments.{key}"), &args).await
+}
+
+impl From<ArgumentError> for CommandError {
+  fn from(err: ArgumentError) -> Self {
+    CommandError::InvalidArgument(err)
+  }
+}
+
 #[derive(Debug)]
-#[allow(dead_code)]
 pub enum CommandError {
   MessageNotSent,
   InvalidData,
@@ -101,7 +236,9 @@ pub enum CommandError {
   TreatedException,
   TooEarly,
   CommandNotFound,
-  Unknown
+  InvalidArgument(ArgumentError),
+  Cooldown,
+  Maintenance
 }
 
 impl CommandError {
@@ -113,7 +250,9 @@ impl CommandError {
       CommandError::TreatedException => "TreatedException",
       CommandError::TooEarly => "TooEarly",
       CommandError::CommandNotFound => "CommandNotFound",
-      _ => "Unknown"
+      CommandError::InvalidArgument(_) => "InvalidArgument",
+      CommandError::Cooldown => "Cooldown",
+      CommandError::Maintenance => "Maintenance"
     }
   }
 }
@@ -125,6 +264,321 @@ impl Display for CommandError {
 }
 
 
+// ==================================
+// registry
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CommandCategory {
+  Utility,
+  Information,
+  Owner
+}
+
+impl CommandCategory {
+  pub fn as_str(&self) -> &str {
+    match self {
+      CommandCategory::Utility => "Utility",
+      CommandCategory::Information => "Information",
+      CommandCategory::Owner => "Owner"
+    }
+  }
+}
+
+/// Where the command is registered as an application command
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SlashScope {
+  /// prefix only
+  Disabled,
+  Global,
+  /// only in the guilds listed by `slash_guilds` in the config
+  Guild
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionKind {
+  String,
+  /// boolean option, written as `--name` for the parser
+  Flag
+}
+
+/// Argument of a command, in the order the parser reads them
+#[derive(Debug, Clone)]
+pub struct CommandOption {
+  pub name: &'static str,
+  pub description: &'static str,
+  pub kind: OptionKind,
+  pub required: bool,
+  pub choices: &'static [&'static str]
+}
+
+impl CommandOption {
+  pub fn new(name: &'static str, description: &'static str, kind: OptionKind, required: bool) -> Self {
+    CommandOption { name, description, kind, required, choices: &[] }
+  }
+
+  pub fn with_choices(self, choices: &'static [&'static str]) -> Self {
+    CommandOption { choices, ..self }
+  }
+}
+
+/// Metadata describing a command, read by the registry to dispatch and list it.
+#[derive(Debug, Clone)]
+pub struct CommandInfo {
+  pub name: &'static str,
+  pub aliases: &'static [&'static str],
+  pub description: &'static str,
+  pub usage: &'static str,
+  pub category: CommandCategory,
+  /// Discord permissions the invoking member must have
+  pub permissions: Permissions,
+  /// Discord permissions the bot must have in the channel
+  pub bot_permissions: Permissions,
+  pub owner_only: bool,
+  pub guild_only: bool,
+  pub dm_only: bool,
+  pub cooldowns: Vec<Cooldown>,
+  /// arguments of the command, used to expose it as an application command
+  pub options: Vec<CommandOption>,
+  pub slash: SlashScope
+}
+
+impl Default for CommandInfo {
+  fn default() -> Self {
+    CommandInfo {
+      name: "",
+      aliases: &[],
+      description: "",
+      usage: "",
+      category: CommandCategory::Utility,
+      permissions: Permissions::empty(),
+      bot_permissions: Permissions::empty(),
+      owner_only: false,
+      guild_only: false,
+      dm_only: false,
+      cooldowns: Vec::new(),
+      options: Vec::new(),
+      slash: SlashScope::Disabled
+    }
+  }
+}
+
+/// Every command under `handle/commands/` implements this trait and is added in [register_commands].
+#[async_trait]
+pub trait Command: Send + Sync {
+  fn info(&self) -> CommandInfo;
+
+  async fn execute(
+    &self,
+    ctx: &Context,
+    source: &CommandSource<'_>,
+    storage: &Storage,
+    command: &CommandData
+  ) -> Result<(), CommandError>;
+}
+
+pub struct RegisteredCommand {
+  pub info: CommandInfo,
+  pub command: Box<dyn Command>
+}
+
+#[derive(Debug)]
+pub enum RegistryError {
+  NameAlreadyUsed(String)
+}
+
+impl RegistryError {
+  pub fn as_str(&self) -> &str {
+    match self {
+      RegistryError::NameAlreadyUsed(_) => "NameAlreadyUsed"
+    }
+  }
+}
+
+pub struct CommandRegistry {
+  commands: Vec<RegisteredCommand>,
+  // name or alias -> index in `commands`
+  names: HashMap<String, usize>
+}
+
+impl CommandRegistry {
+  fn new() -> Self {
+    Self { commands: Vec::new(), names: HashMap::new() }
+  }
+
+  fn register(&mut self, command: impl Command + 'static) -> Result<(), RegistryError> {
+    let info = command.info();
+
+    for name in std::iter::once(&info.name).chain(info.aliases.iter()) {
+      if self.names.contains_key(*name) {
+        return Err(RegistryError::NameAlreadyUsed(name.to_string()));
+      }
+    }
+
+    let index = self.commands.len();
+    for name in std::iter::once(&info.name).chain(info.aliases.iter()) {
+      self.names.insert(name.to_string(), index);
+    }
+    self.commands.push(RegisteredCommand { info, command: Box::new(command) });
+    Ok(())
+  }
+
+  /// Find a command by its name or one of its aliases
+  pub fn get(&self, name: &str) -> Option<&RegisteredCommand> {
+    self.names.get(name).and_then(|index| self.commands.get(*index))
+  }
+
+  /// Same as [CommandRegistry::get], ignoring the case of `name` when `case_insensitive` is set
+  pub fn find(&self, name: &str, case_insensitive: bool) -> Option<&RegisteredCommand> {
+    if case_insensitive { self.get(&name.to_lowercase()) } else { self.get(name) }
+  }
+
+  /// Closest name or alias to `name`, if the edit distance is small enough to be a typo
+  pub fn suggest(&self, name: &str) -> Option<&str> {
+    let max_distance = match name.chars().count() {
+      0..=2 => 1,
+      3..=5 => 2,
+      _ => 3
+    };
+
+    self.names.keys()
+      .map(|known| (known, edit_distance(name, known)))
+      .filter(|(_, distance)| *distance <= max_distance)
+      .min_by_key(|(known, distance)| (*distance, known.len()))
+      .map(|(known, _)| known.as_str())
+  }
+
+  pub fn list(&self) -> &Vec<RegisteredCommand> {
+    &self.commands
+  }
+}
+
+/// Levenshtein distance between two strings
+fn edit_distance(a: &str, b: &str) -> usize {
+  let b_chars: Vec<char> = b.chars().collect();
+  let mut previous: Vec<usize> = (0..=b_chars.len()).collect();
+
+  for (i, ca) in a.chars().enumerate() {
+    let mut current = vec![i + 1; b_chars.len() + 1];
+    for (j, cb) in b_chars.iter().enumerate() {
+      let cost = if ca == *cb { 0 } else { 1 };
+      current[j + 1] = (previous[j] + cost).min(previous[j + 1] + 1).min(current[j] + 1);
+    }
+    previous = current;
+  }
+
+  previous[b_chars.len()]
+}
+
+pub static REGISTRY: Lazy<CommandRegistry> = Lazy::new(|| {
+  let mut registry = CommandRegistry::new();
+  register_commands(&mut registry);
+  registry
+});
+
+fn register_commands(registry: &mut CommandRegistry) {
+  let results = vec![
+    registry.register(ping::Ping),
+    registry.register(prefix::Prefix),
+    registry.register(help::Help),
+    registry.register(maintenance::MaintenanceCommand),
+    registry.register(status::StatusCommand),
+    registry.register(stats::Stats),
+    registry.register(reload::Reload)
+  ];
+
+  for result in results {
+    if let Err(err) = result {
+      match &err {
+        RegistryError::NameAlreadyUsed(name) => {
+          utils::error("CommandRegistry", format!("Cannot register the command `{}`", name).as_str(), err.as_str())
+        }
+      }
+    }
+  }
+}
+
+
 // ==================================
 // declare commands
-pub mod ping;
\ No newline at end of file
+pub mod ping;
+pub mod prefix;
+pub mod help;
+pub mod maintenance;
+pub mod status;
+pub mod stats;
+pub mod reload;
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  struct Dummy(&'static str, &'static [&'static str]);
+
+  #[async_trait]
+  impl Command for Dummy {
+    fn info(&self) -> CommandInfo {
+      CommandInfo { name: self.0, aliases: self.1, ..Default::default() }
+    }
+
+    async fn execute(&self, _: &Context, _: &CommandSource<'_>, _: &Storage, _: &CommandData) -> Result<(), CommandError> {
+      Ok(())
+    }
+  }
+
+  fn registry() -> CommandRegistry {
+    let mut registry = CommandRegistry::new();
+    registry.register(Dummy("ping", &[])).unwrap();
+    registry.register(Dummy("prefix", &["px"])).unwrap();
+    registry.register(Dummy("maintenance", &["mt"])).unwrap();
+    registry.register(Dummy("status", &[])).unwrap();
+    registry.register(Dummy("stats", &[])).unwrap();
+    registry
+  }
+
+  #[test]
+  fn edit_distance_counts_the_edits() {
+    assert_eq!(edit_distance("", ""), 0);
+    assert_eq!(edit_distance("ping", "ping"), 0);
+    assert_eq!(edit_distance("", "ping"), 4);
+    assert_eq!(edit_distance("ping", ""), 4);
+    assert_eq!(edit_distance("pign", "ping"), 2);
+    assert_eq!(edit_distance("pin", "ping"), 1);
+    assert_eq!(edit_distance("pingg", "ping"), 1);
+    assert_eq!(edit_distance("kitten", "sitting"), 3);
+    assert_eq!(edit_distance("état", "etat"), 1);
+  }
+
+  #[test]
+  fn suggest_finds_typos() {
+    let registry = registry();
+    assert_eq!(registry.suggest("pnig"), Some("ping"));
+    assert_eq!(registry.suggest("prefx"), Some("prefix"));
+    assert_eq!(registry.suggest("maintenace"), Some("maintenance"));
+    assert_eq!(registry.suggest("pz"), Some("px"));
+  }
+
+  #[test]
+  fn suggest_prefers_the_closest_then_the_shortest() {
+    let registry = registry();
+    assert_eq!(registry.suggest("stat"), Some("stats"));
+    assert_eq!(registry.suggest("sttus"), Some("status"));
+    // `statu` is 1 edit away from both, the shortest wins
+    assert_eq!(registry.suggest("statu"), Some("stats"));
+    // 2 edits is too far for a 2 letters name
+    assert_eq!(registry.suggest("ab"), None);
+  }
+
+  #[test]
+  fn suggest_stays_silent_when_nothing_is_close() {
+    let registry = registry();
+    assert_eq!(registry.suggest("hello"), None);
+    assert_eq!(registry.suggest("configuration"), None);
+  }
+
+  #[test]
+  fn duplicated_names_are_refused() {
+    let mut registry = registry();
+    assert!(matches!(registry.register(Dummy("pong", &["ping"])), Err(RegistryError::NameAlreadyUsed(name)) if name == "ping"));
+    assert!(registry.get("pong").is_none());
+  }
+}
@@ -1,38 +1,51 @@
-use serenity::{ model::prelude::Message, http::CacheHttp, prelude::Context };
-use crate::{Storage, utils};
-use super::{CommandData, CommandError};
+use serenity::{ async_trait, prelude::Context };
+use crate::Storage;
+use crate::handle::{ cooldowns::Cooldown, source::CommandSource };
+use super::{Command, CommandCategory, CommandData, CommandError, CommandInfo, SlashScope};
 
-pub (in crate::handle) async fn execute(
-  ctx: &Context,
-  http: &impl CacheHttp,
-  message: &Message,
-  storage: &Storage,
-  _command: &CommandData
-) -> Result<(), CommandError> {
-  if let Some(act_shard) = storage.latency.get(&ctx.shard_id) {
-    if act_shard.ping.as_nanos() > 0 {
-      let msg = message.reply(
-        http,
-        format!("🏓 **Pong!**, j'ai une latence de `{l}ms` (shard: {id}) !", l = act_shard.ping.as_millis(), id = ctx.shard_id).as_str()
-      ).await;
-      if let Err(why) = msg {
-        utils::error("MessageSender", "An error occured while sending the message", why.to_string().as_str());
+pub struct Ping;
+
+#[async_trait]
+impl Command for Ping {
+  fn info(&self) -> CommandInfo {
+    CommandInfo {
+      name: "ping",
+      aliases: &["p", "latency"],
+      description: "Affiche la latence du shard actuel",
+      usage: "ping",
+      category: CommandCategory::Information,
+      cooldowns: vec![Cooldown::user(5).with_burst(2), Cooldown::channel(2)],
+      slash: SlashScope::Global,
+      ..Default::default()
+    }
+  }
+
+  async fn execute(
+    &self,
+    ctx: &Context,
+    source: &CommandSource<'_>,
+    storage: &Storage,
+    _command: &CommandData
+  ) -> Result<(), CommandError> {
+    if let Some(act_shard) = storage.latency.get(&ctx.shard_id) {
+      if act_shard.ping.as_nanos() > 0 {
+        source.reply(
+          ctx,
+          format!("🏓 **Pong!**, j'ai une latence de `{l}ms` (shard: {id}) !", l = act_shard.ping.as_millis(), id = ctx.shard_id)
+        ).await?;
+      } else {
+        let _ = too_early(ctx, source).await;
+        return Err(CommandError::TooEarly)
       }
     } else {
-      let _ = too_early(http, message).await;
-      return Err(CommandError::TooEarly)
-    }
-  } else {
-    let _ = too_early(http, message).await;
-    return Err(CommandError::InvalidData)
-  };
+      let _ = too_early(ctx, source).await;
+      return Err(CommandError::InvalidData)
+    };
 
-  Ok(())
+    Ok(())
+  }
 }
 
-async fn too_early(http: &impl CacheHttp, message: &Message) -> Result<(), String> {
-  if let Err(why) = message.reply(http, "> 🦀 ** ** **Je démarre encore.**\nIl me faut encore 1m pour me réveiller complètement.").await {
-    utils::error("MessageSender", "An error occured while sending the message", why.to_string().as_str());
-    Err(why.to_string())
-  } else { Ok(()) }
-}
+async fn too_early(ctx: &Context, source: &CommandSource<'_>) -> Result<(), CommandError> {
+  source.reply(ctx, "> 🦀 ** ** **Je démarre encore.**\nIl me faut encore 1m pour me réveiller complètement.").await
+}
\ No newline at end of file
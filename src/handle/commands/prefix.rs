@@ -0,0 +1,115 @@
+use serenity::{ async_trait, prelude::Context };
+use crate::{ Storage, utils, storage::Database, libs::i18n };
+use crate::handle::{ checks, prefixes::{ GuildPrefixes, PrefixError, MAX_PREFIXES_PER_GUILD, MAX_PREFIX_LEN } };
+use crate::handle::{ cooldowns::Cooldown, source::CommandSource };
+use super::{Command, CommandCategory, CommandData, CommandError, CommandInfo, CommandOption, OptionKind, SlashScope};
+
+pub struct Prefix;
+
+#[async_trait]
+impl Command for Prefix {
+  fn info(&self) -> CommandInfo {
+    CommandInfo {
+      name: "prefix",
+      aliases: &["prefixes"],
+      description: "Affiche ou modifie les préfixes du serveur",
+      usage: "prefix [add <préfixe> | remove <préfixe> | reset]",
+      category: CommandCategory::Utility,
+      guild_only: true,
+      cooldowns: vec![Cooldown::user(3), Cooldown::guild(10).with_burst(5)],
+      options: vec![
+        CommandOption::new("action", "Action à effectuer", OptionKind::String, false).with_choices(&["add", "remove", "reset"]),
+        CommandOption::new("prefix", "Préfixe à ajouter ou retirer", OptionKind::String, false)
+      ],
+      slash: SlashScope::Global,
+      ..Default::default()
+    }
+  }
+
+  async fn execute(
+    &self,
+    ctx: &Context,
+    source: &CommandSource<'_>,
+    storage: &Storage,
+    command: &CommandData
+  ) -> Result<(), CommandError> {
+    let guild_id = source.guild_id().ok_or(CommandError::InvalidData)?;
+    let lang = source.lang();
+
+    let mut args = command.parse_args();
+    let action = args.optional(|a| a.string("action"))?;
+
+    let prefixes_lock = {
+      let data = ctx.data.read().await;
+      data.get::<GuildPrefixes>().expect("Expected GuildPrefixes in TypeMap.").clone()
+    };
+
+    let action = match action {
+      Some(action) => action.to_lowercase(),
+      None => {
+        let prefixes = prefixes_lock.read().await;
+        let list = match prefixes.get(guild_id) {
+          Some(custom) => custom.iter().map(|p| format!("`{p}`")).collect::<Vec<String>>().join(", "),
+          None => i18n::translate(&lang, "prefix.default", &[("prefix", storage.client.prefix.clone())]).await
+        };
+        drop(prefixes);
+        source.reply(ctx, i18n::translate(&lang, "prefix.list", &[("prefixes", list)]).await).await?;
+        return Ok(())
+      }
+    };
+
+    // anyone can see the prefixes, only managers can change them
+    let permissions = checks::permissions_in_channel(ctx, source, source.author().id).await.ok().flatten();
+    if !checks::is_owner(storage, source.author().id) && !permissions.map(|p| p.manage_guild()).unwrap_or(false) {
+      source.reply(ctx, i18n::translate(&lang, "prefix.manage_guild_required", &[]).await).await?;
+      return Err(CommandError::NoPermissions)
+    }
+
+    let db = {
+      let data = ctx.data.read().await;
+      data.get::<Database>().expect("Expected Database in TypeMap.").clone()
+    };
+    let mut conn = db.lock().await;
+    let mut prefixes = prefixes_lock.write().await;
+
+    let (result, done, value) = match action.as_str() {
+      "add" => {
+        let value = args.string("préfixe")?.trim().to_string();
+        (prefixes.add(&mut conn, guild_id, &value).await, "prefix.added", value)
+      }
+      "remove" | "delete" => {
+        let value = args.string("préfixe")?.trim().to_string();
+        (prefixes.remove(&mut conn, guild_id, &value).await, "prefix.removed", value)
+      }
+      "reset" => (prefixes.reset(&mut conn, guild_id).await, "prefix.reset", storage.client.prefix.clone()),
+      _ => {
+        drop(prefixes);
+        drop(conn);
+        let usage = format!("{}{}", command.prefix, super::usage(&lang, &self.info()).await);
+        source.reply(ctx, i18n::translate(&lang, "prefix.unknown_action", &[("usage", usage)]).await).await?;
+        return Err(CommandError::TreatedException)
+      }
+    };
+    drop(prefixes);
+    drop(conn);
+
+    match result {
+      Ok(()) => source.reply(ctx, i18n::translate(&lang, done, &[("prefix", value)]).await).await,
+      Err(err) => {
+        let (key, args) = match &err {
+          PrefixError::TooMany => ("too_many", vec![("max", MAX_PREFIXES_PER_GUILD.to_string())]),
+          PrefixError::TooLong => ("too_long", vec![("max", MAX_PREFIX_LEN.to_string())]),
+          PrefixError::Empty => ("empty", vec![]),
+          PrefixError::AlreadyExists => ("already_exists", vec![]),
+          PrefixError::NotFound => ("not_found", vec![]),
+          PrefixError::Database(why) => {
+            utils::error("GuildPrefixes", "Cannot save the prefixes", why.as_str());
+            ("database", vec![])
+          }
+        };
+        source.reply(ctx, i18n::translate(&lang, &format!("prefix.errors.{key}"), &args).await).await?;
+        Err(CommandError::TreatedException)
+      }
+    }
+  }
+}
@@ -0,0 +1,84 @@
+use serenity::{ async_trait, prelude::Context };
+use crate::{ Storage, utils, reload, libs::i18n };
+use crate::handle::source::CommandSource;
+use super::{Command, CommandCategory, CommandData, CommandError, CommandInfo, CommandOption, OptionKind};
+
+pub struct Reload;
+
+#[async_trait]
+impl Command for Reload {
+  fn info(&self) -> CommandInfo {
+    CommandInfo {
+      name: "reload",
+      aliases: &["rl"],
+      description: "Recharge la configuration sans redémarrer",
+      usage: "reload [--dry-run]",
+      category: CommandCategory::Owner,
+      owner_only: true,
+      options: vec![
+        CommandOption::new("dry-run", "Affiche les changements sans les appliquer", OptionKind::Flag, false)
+      ],
+      ..Default::default()
+    }
+  }
+
+  async fn execute(
+    &self,
+    ctx: &Context,
+    source: &CommandSource<'_>,
+    _storage: &Storage,
+    command: &CommandData
+  ) -> Result<(), CommandError> {
+    let lang = source.lang();
+    let dry_run = command.parse_args().flag("dry-run", Some("n"));
+
+    let pending = match reload::prepare(&ctx.data).await {
+      Ok(pending) => pending,
+      Err(problems) => {
+        let list = problems.iter().map(|p| format!("• {p}")).collect::<Vec<String>>().join("\n");
+        source.reply(ctx, i18n::translate(&lang, "reload.invalid", &[("problems", list)]).await).await?;
+        return Err(CommandError::TreatedException)
+      }
+    };
+
+    let mut lines = Vec::new();
+    if pending.report.is_empty() {
+      lines.push(i18n::translate(&lang, "reload.unchanged", &[]).await);
+    } else {
+      if !dry_run {
+        utils::info("ConfigReload", format!("Reload requested by {}", source.author().tag()).as_str());
+      }
+      if !pending.report.applied.is_empty() {
+        let key = if dry_run { "reload.would_apply" } else { "reload.scheduled" };
+        lines.push(i18n::translate(&lang, key, &[("keys", keys(&pending.report.applied))]).await);
+      }
+      if !pending.report.restart.is_empty() {
+        lines.push(i18n::translate(&lang, "reload.restart", &[("keys", keys(&pending.report.restart))]).await);
+      }
+    }
+
+    if dry_run {
+      lines.push(i18n::translate(&lang, "reload.dry_run", &[]).await);
+      return source.reply(ctx, lines.join("\n")).await
+    }
+
+    // the storage is held by this command, the changes are written once it is released
+    // and confirmed by a second message
+    let (data, http, channel_id) = (ctx.data.clone(), ctx.http.clone(), source.channel_id());
+    let applied = pending.report.applied.clone();
+    tokio::spawn(async move {
+      pending.apply(&data).await;
+      if applied.is_empty() { return }
+      let content = i18n::translate(&lang, "reload.applied", &[("keys", keys(&applied))]).await;
+      if let Err(why) = channel_id.say(&http, content).await {
+        utils::warn_with_cause("ConfigReload", "Cannot confirm the reload", why.to_string().as_str());
+      }
+    });
+
+    source.reply(ctx, lines.join("\n")).await
+  }
+}
+
+fn keys(keys: &[String]) -> String {
+  keys.iter().map(|k| format!("`{k}`")).collect::<Vec<String>>().join(", ")
+}
@@ -0,0 +1,128 @@
+use std::time::Instant;
+use chrono::Utc;
+use serenity::{ async_trait, builder::CreateEmbed, prelude::Context };
+use crate::{ Storage, status::format_uptime, libs::i18n };
+use crate::handle::{ cooldowns::Cooldown, hooks::CommandUsage, source::CommandSource };
+use super::{Command, CommandCategory, CommandData, CommandError, CommandInfo, SlashScope};
+
+const EMBED_COLOR: u32 = 0xC77DFF;
+/// Shards listed in the embed, the others are summarized
+const MAX_SHARDS_SHOWN: usize = 10;
+
+pub struct Stats;
+
+#[async_trait]
+impl Command for Stats {
+  fn info(&self) -> CommandInfo {
+    CommandInfo {
+      name: "stats",
+      aliases: &["botinfo", "info"],
+      description: "Affiche les statistiques et la latence du bot",
+      usage: "stats",
+      category: CommandCategory::Information,
+      cooldowns: vec![Cooldown::user(10), Cooldown::channel(3)],
+      slash: SlashScope::Global,
+      ..Default::default()
+    }
+  }
+
+  async fn execute(
+    &self,
+    ctx: &Context,
+    source: &CommandSource<'_>,
+    storage: &Storage,
+    _command: &CommandData
+  ) -> Result<(), CommandError> {
+    let lang = source.lang();
+    source.reply(ctx, i18n::translate(&lang, "stats.computing", &[]).await).await?;
+
+    let usage_lock = {
+      let data = ctx.data.read().await;
+      data.get::<CommandUsage>().expect("Expected CommandUsage in TypeMap.").clone()
+    };
+    let (total, failed, top) = {
+      let usage = usage_lock.lock().await;
+      (usage.total, usage.failed, usage.top(3))
+    };
+
+    let uptime = (Utc::now() - storage.process_start).to_std().unwrap_or_default();
+    let memory = match memory_usage() {
+      Some(bytes) => i18n::translate(&lang, "stats.megabytes", &[("size", format!("{:.1}", bytes as f64 / (1024.0 * 1024.0)))]).await,
+      None => i18n::translate(&lang, "stats.memory_unavailable", &[]).await
+    };
+
+    let mut labels: Vec<String> = Vec::new();
+    for key in ["title", "rest", "uptime", "memory", "guilds", "users", "channels", "gateway", "commands"] {
+      labels.push(i18n::translate(&lang, format!("stats.{key}").as_str(), &[]).await);
+    }
+    let (gateway, usage) = (shards(&lang, storage, ctx.shard_id).await, commands(&lang, total, failed, &top).await);
+    let embed = |rest: String| {
+      let mut embed = CreateEmbed::default();
+      embed.color(EMBED_COLOR)
+        .title(&labels[0])
+        .field(&labels[1], rest, true)
+        .field(&labels[2], format_uptime(uptime), true)
+        .field(&labels[3], &memory, true)
+        .field(&labels[4], storage.client.guilds.len().to_string(), true)
+        .field(&labels[5], storage.client.user_count().to_string(), true)
+        .field(&labels[6], storage.client.channel_count().to_string(), true)
+        .field(&labels[7], &gateway, false)
+        .field(&labels[8], &usage, false)
+        .footer(|f| f.text(format!("MioEngine v{} ({})", storage.client.version, storage.client.build_type)));
+      embed
+    };
+
+    // the round-trip of an edit is the REST latency, the first reply of an interaction also waits for its acknowledgement
+    let start = Instant::now();
+    source.edit_reply_embed(ctx, embed("`…`".to_string())).await?;
+    let rest = start.elapsed();
+
+    source.edit_reply_embed(ctx, embed(format!("`{}ms`", rest.as_millis()))).await
+  }
+}
+
+/// One line per shard: latency, history and watchdog restarts
+async fn shards(lang: &str, storage: &Storage, current: u64) -> String {
+  if storage.latency.is_empty() { return i18n::translate(lang, "stats.no_samples", &[]).await }
+
+  let mut ids: Vec<&u64> = storage.latency.keys().collect();
+  ids.sort();
+  let mut lines: Vec<String> = Vec::new();
+  for id in ids.iter().take(MAX_SHARDS_SHOWN) {
+    let latency = &storage.latency[*id];
+    let marker = if **id == current { "▶" } else { " " };
+    let state = if latency.warned { "🔴" } else { "🟢" };
+    let mut line = format!("`{marker} #{id}` {state} `{}ms`", latency.ping.as_millis());
+    if let Some(stats) = latency.history.stats() {
+      let average = i18n::translate(lang, "stats.average", &[("avg", stats.avg.as_millis().to_string())]).await;
+      line.push_str(&format!(" · {average} · p95 {}ms", stats.p95.as_millis()));
+    }
+    if latency.restarts > 0 {
+      let restarts = i18n::translate(lang, "stats.restarts", &[("count", latency.restarts.to_string())]).await;
+      line.push_str(&format!(" · {restarts}"));
+    }
+    lines.push(line);
+  }
+
+  if ids.len() > MAX_SHARDS_SHOWN {
+    lines.push(i18n::translate(lang, "stats.more_shards", &[("count", (ids.len() - MAX_SHARDS_SHOWN).to_string())]).await);
+  }
+  lines.join("\n")
+}
+
+async fn commands(lang: &str, total: u64, failed: u64, top: &[(&'static str, u64)]) -> String {
+  let mut content = i18n::translate(lang, "stats.usage", &[("total", total.to_string()), ("failed", failed.to_string())]).await;
+  if !top.is_empty() {
+    let top = top.iter().map(|(name, uses)| format!("`{name}` ({uses})")).collect::<Vec<String>>().join(", ");
+    content.push_str(&format!("\n{}", i18n::translate(lang, "stats.top", &[("commands", top)]).await));
+  }
+  content
+}
+
+/// Resident memory of the process, only known on Linux
+fn memory_usage() -> Option<u64> {
+  let status = std::fs::read_to_string("/proc/self/status").ok()?;
+  let line = status.lines().find(|l| l.starts_with("VmRSS:"))?;
+  let kb = line.split_whitespace().nth(1)?.parse::<u64>().ok()?;
+  Some(kb * 1024)
+}
@@ -0,0 +1,256 @@
+use serenity::{ async_trait, prelude::Context };
+use crate::{ Storage, utils, libs::i18n };
+use crate::status::{ self, ClientActivityType, Placeholders, RotationMode, Schedule, Snapshot, Status, StatusManager, MIN_STATUS_TIME };
+use crate::handle::{ args::{ Args, ArgumentError, ArgumentErrorKind }, cooldowns, source::CommandSource };
+use super::{Command, CommandCategory, CommandData, CommandError, CommandInfo};
+
+const STATUS_TYPES: [&str; 4] = ["playing", "streaming", "listening", "watching"];
+const ONLINE_STATUSES: [&str; 4] = ["online", "idle", "dnd", "invisible"];
+const MODES: [&str; 3] = ["sequential", "random", "weighted"];
+
+pub struct StatusCommand;
+
+#[async_trait]
+impl Command for StatusCommand {
+  fn info(&self) -> CommandInfo {
+    CommandInfo {
+      name: "status",
+      aliases: &["statuses", "activity"],
+      description: "Gère les statuts affichés par le bot",
+      usage: "status [list | add [--weight <n>] [--online <statut>] [--from <MM-JJ> --until <MM-JJ>] [--hours <HH-HH>] <type> <message> | remove <n> | move <n> <position> | preview <n | message> | interval <durée> | mode <sequential|random|weighted> | next]",
+      category: CommandCategory::Owner,
+      owner_only: true,
+      ..Default::default()
+    }
+  }
+
+  async fn execute(
+    &self,
+    ctx: &Context,
+    source: &CommandSource<'_>,
+    storage: &Storage,
+    command: &CommandData
+  ) -> Result<(), CommandError> {
+    let status_lock = {
+      let data = ctx.data.read().await;
+      data.get::<StatusManager>().expect("Expected StatusManager in TypeMap.").clone()
+    };
+
+    let lang = source.lang();
+    let mut args = command.parse_args();
+    let action = args.optional(|a| a.string("action"))?.map(|a| a.to_lowercase());
+
+    let mut manager = status_lock.write().await;
+    let content = match action.as_deref() {
+      None | Some("list") => {
+        let content = describe(&lang, &manager).await;
+        drop(manager);
+        source.reply(ctx, content).await?;
+        return Ok(())
+      }
+      Some("preview") => {
+        let template = match args.optional(|a| a.rest("statut"))? {
+          Some(value) => match value.parse::<usize>() {
+            Ok(position) => match position.checked_sub(1).and_then(|i| manager.list.get(i)) {
+              Some(status) => status.message.clone(),
+              None => return Err(out_of_range("statut", &value, manager.list.len()))
+            },
+            Err(_) => value
+          },
+          None => match manager.list.get(manager.index) {
+            Some(status) => status.message.clone(),
+            None => {
+              drop(manager);
+              source.reply(ctx, i18n::translate(&lang, "status.nothing_to_preview", &[]).await).await?;
+              return Ok(())
+            }
+          }
+        };
+        drop(manager);
+        let shards = storage.latency.len().max(1);
+        let ping = storage.latency.get(&ctx.shard_id).map(|l| l.ping);
+        let rendered = status::render(&template, &Placeholders::new(&Snapshot::capture(storage, false), ctx.shard_id, shards, ping));
+        let content = i18n::translate(&lang, "status.preview", &[("shard", ctx.shard_id.to_string()), ("status", rendered)]).await;
+        source.reply(ctx, content).await?;
+        return Ok(())
+      }
+      Some("add") => {
+        let status = match parse_add(&mut args)? {
+          Some(status) => status,
+          None => {
+            drop(manager);
+            source.reply(ctx, i18n::translate(&lang, "status.invalid_schedule", &[]).await).await?;
+            return Err(CommandError::TreatedException)
+          }
+        };
+        let content = i18n::translate(&lang, "status.added", &[
+          ("position", (manager.list.len() + 1).to_string()),
+          ("status", describe_status(&lang, &status).await)
+        ]).await;
+        manager.add(status);
+        content
+      }
+      Some("remove" | "delete") => {
+        let position = args.integer_in("statut", 1, manager.list.len().max(1) as i64)?;
+        match manager.remove(position as usize - 1) {
+          Some(removed) => i18n::translate(&lang, "status.removed", &[("status", describe_status(&lang, &removed).await)]).await,
+          None => return Err(out_of_range("statut", &position.to_string(), manager.list.len()))
+        }
+      }
+      Some("move") => {
+        let max = manager.list.len().max(1) as i64;
+        let from = args.integer_in("statut", 1, max)?;
+        let to = args.integer_in("position", 1, max)?;
+        if !manager.reorder(from as usize - 1, to as usize - 1) {
+          return Err(out_of_range("statut", &from.to_string(), manager.list.len()))
+        }
+        let moved = i18n::translate(&lang, "status.moved", &[("position", to.to_string())]).await;
+        format!("{moved}\n{}", describe(&lang, &manager).await)
+      }
+      Some("interval") => {
+        let interval = args.duration("intervalle")?;
+        if interval.as_secs() < MIN_STATUS_TIME {
+          drop(manager);
+          source.reply(ctx, i18n::translate(&lang, "status.interval_too_short", &[("min", MIN_STATUS_TIME.to_string())]).await).await?;
+          return Err(CommandError::TreatedException)
+        }
+        manager.set_interval(interval.as_secs());
+        i18n::translate(&lang, "status.interval", &[("interval", cooldowns::format_remaining(interval))]).await
+      }
+      Some("mode") => {
+        let value = args.string("mode")?;
+        manager.mode = RotationMode::from_str(&value).ok_or_else(|| choice_error("mode", &value, &MODES))?;
+        i18n::translate(&lang, "status.mode", &[("mode", manager.mode.as_str().to_string())]).await
+      }
+      Some("next" | "rotate") => {
+        manager.rotate_now();
+        drop(manager);
+        source.reply(ctx, i18n::translate(&lang, "status.next", &[]).await).await?;
+        return Ok(())
+      }
+      Some(_) => {
+        drop(manager);
+        let usage = format!("{}{}", command.prefix, super::usage(&lang, &self.info()).await);
+        source.reply(ctx, i18n::translate(&lang, "status.unknown_action", &[("usage", usage)]).await).await?;
+        return Err(CommandError::TreatedException)
+      }
+    };
+
+    if let Err(err) = manager.save() {
+      utils::error("StatusLoop", "cannot save the statuses", err.as_str());
+    }
+    utils::info("StatusLoop", format!("Statuses updated by {}", source.author().tag()).as_str());
+    drop(manager);
+
+    source.reply(ctx, content).await
+  }
+}
+
+/// Status described by the arguments of `add`, `None` when its schedule is invalid
+fn parse_add(args: &mut Args) -> Result<Option<Status>, ArgumentError> {
+  // the options are read first, they are not part of the message
+  let weight = args.option_integer("weight", Some("w"))?.unwrap_or(1);
+  let online = args.option("online", None)?;
+  let (from, until, hours) = (args.option("from", None)?, args.option("until", None)?, args.option("hours", None)?);
+  let status_type = parse_type(args)?;
+  let message = args.rest("message")?;
+
+  let mut status = Status::new(message, status_type);
+  status.weight = weight.clamp(0, u32::MAX as i64) as u32;
+  if let Some(online) = online {
+    status.online_status = status::online_status_from_str(&online).ok_or_else(|| choice_error("online", &online, &ONLINE_STATUSES))?;
+  }
+  match Schedule::parse(from.as_deref(), until.as_deref(), hours.as_deref()) {
+    Ok(schedule) => status.schedule = schedule,
+    Err(_) => return Ok(None)
+  }
+  Ok(Some(status))
+}
+
+fn parse_type(args: &mut Args) -> Result<ClientActivityType, ArgumentError> {
+  let value = args.string("type")?;
+  let status_type = ClientActivityType::from_str(&value);
+  if status_type.is_unknown() {
+    return Err(choice_error("type", &value, &STATUS_TYPES))
+  }
+  Ok(status_type)
+}
+
+fn choice_error(name: &str, value: &str, choices: &[&str]) -> ArgumentError {
+  let choices = choices.iter().map(|c| c.to_string()).collect();
+  ArgumentError::new(name, 2, Some(value), ArgumentErrorKind::InvalidChoice(choices))
+}
+
+fn out_of_range(name: &str, value: &str, len: usize) -> CommandError {
+  ArgumentError::new(name, 2, Some(value), ArgumentErrorKind::OutOfRange(1, len.max(1) as i64)).into()
+}
+
+async fn describe(lang: &str, manager: &StatusManager) -> String {
+  let state = if manager.continue_status { "status.enabled" } else { "status.disabled" };
+  let mut lines = vec![i18n::translate(lang, "status.rotation", &[
+    ("state", i18n::translate(lang, state, &[]).await),
+    ("mode", manager.mode.as_str().to_string()),
+    ("interval", cooldowns::format_remaining(std::time::Duration::from_secs(manager.status_time)))
+  ]).await];
+  if manager.list.is_empty() {
+    lines.push(i18n::translate(lang, "status.empty", &[]).await);
+  }
+  for (i, status) in manager.list.iter().enumerate() {
+    let current = if i == manager.index { "▶" } else { " " };
+    lines.push(format!("`{current} {}.` {}", i + 1, describe_status(lang, status).await));
+  }
+  lines.join("\n")
+}
+
+async fn describe_status(lang: &str, status: &Status) -> String {
+  let mut details = Vec::new();
+  if status.online_status.name() != "online" { details.push(status.online_status.name().to_string()) }
+  if status.weight != 1 { details.push(i18n::translate(lang, "status.weight", &[("weight", status.weight.to_string())]).await) }
+  if let Some(schedule) = status.schedule {
+    if let (Some(from), Some(until)) = (schedule.start_date(), schedule.end_date()) {
+      details.push(i18n::translate(lang, "status.schedule.dates", &[("from", from), ("until", until)]).await)
+    }
+    if let Some((from, until)) = schedule.hours {
+      details.push(i18n::translate(lang, "status.schedule.hours", &[("from", from.to_string()), ("until", until.to_string())]).await)
+    }
+  }
+
+  let details = if details.is_empty() { String::new() } else { format!(" *({})*", details.join(", ")) };
+  format!("**{}** {}{details}", status.status_type.as_str(), status.message)
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn add(raw: &str) -> Status {
+    parse_add(&mut Args::new(raw)).unwrap().unwrap()
+  }
+
+  #[test]
+  fn add_options_are_not_part_of_the_message() {
+    let status = add("watching hello --weight 2");
+    assert_eq!(status.message, "hello");
+    assert_eq!(status.weight, 2);
+    assert_eq!(status.status_type.as_str(), "WATCHING");
+
+    let status = add("--online idle playing au poker 🃏 --hours 22-6");
+    assert_eq!(status.message, "au poker 🃏");
+    assert_eq!(status.online_status.name(), "idle");
+    assert!(status.schedule.is_some());
+  }
+
+  #[test]
+  fn add_keeps_the_message_as_typed() {
+    assert_eq!(add("listening \"le silence\"  de la nuit").message, "le silence  de la nuit");
+  }
+
+  #[test]
+  fn add_refuses_invalid_arguments() {
+    assert!(parse_add(&mut Args::new("dancing hello")).is_err());
+    assert!(parse_add(&mut Args::new("watching")).is_err());
+    assert!(parse_add(&mut Args::new("--online away watching hello")).is_err());
+    assert!(matches!(parse_add(&mut Args::new("--from 12-20 watching hello")), Ok(None)));
+  }
+}
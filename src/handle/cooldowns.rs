@@ -0,0 +1,258 @@
+use std::{ sync::Arc, collections::{ HashMap, VecDeque }, time::{ Duration, Instant } };
+use serenity::prelude::{ TypeMapKey, Mutex };
+
+use super::source::CommandSource;
+
+/// Expired buckets are cleaned when the map grows past this size
+const CLEANUP_THRESHOLD: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CooldownBucket {
+  User,
+  Channel,
+  Guild
+}
+
+impl CooldownBucket {
+  pub fn as_str(&self) -> &str {
+    match self {
+      CooldownBucket::User => "User",
+      CooldownBucket::Channel => "Channel",
+      CooldownBucket::Guild => "Guild"
+    }
+  }
+
+  /// Id of the bucket for this command, the guild bucket falls back on the channel in DMs
+  fn key(&self, source: &CommandSource<'_>) -> u64 {
+    match self {
+      CooldownBucket::User => source.author().id.0,
+      CooldownBucket::Channel => source.channel_id().0,
+      CooldownBucket::Guild => source.guild_id().map(|g| g.0).unwrap_or(source.channel_id().0)
+    }
+  }
+}
+
+/// `burst` uses are allowed per `duration` in each bucket
+#[derive(Debug, Clone, Copy)]
+pub struct Cooldown {
+  pub bucket: CooldownBucket,
+  pub duration: Duration,
+  pub burst: usize
+}
+
+impl Cooldown {
+  pub const fn user(secs: u64) -> Self {
+    Cooldown { bucket: CooldownBucket::User, duration: Duration::from_secs(secs), burst: 1 }
+  }
+
+  pub const fn channel(secs: u64) -> Self {
+    Cooldown { bucket: CooldownBucket::Channel, duration: Duration::from_secs(secs), burst: 1 }
+  }
+
+  pub const fn guild(secs: u64) -> Self {
+    Cooldown { bucket: CooldownBucket::Guild, duration: Duration::from_secs(secs), burst: 1 }
+  }
+
+  pub const fn with_burst(self, burst: usize) -> Self {
+    Cooldown { burst, ..self }
+  }
+}
+
+#[derive(Debug, Default)]
+struct Usage {
+  uses: VecDeque<Instant>,
+  /// the user was already told to wait since the last accepted use
+  notified: bool
+}
+
+impl Usage {
+  fn prune(&mut self, now: Instant, duration: Duration) {
+    while let Some(first) = self.uses.front() {
+      if now.duration_since(*first) >= duration { self.uses.pop_front(); } else { break; }
+    }
+  }
+}
+
+/// Refused use: how long to wait, and whether the user should be told about it
+#[derive(Debug, Clone, Copy)]
+pub struct CooldownHit {
+  pub remaining: Duration,
+  pub notify: bool
+}
+
+#[derive(Debug, Default)]
+pub struct Cooldowns {
+  usages: HashMap<(String, CooldownBucket, u64), Usage>
+}
+
+impl TypeMapKey for Cooldowns {
+  type Value = Arc<Mutex<Cooldowns>>;
+}
+
+impl Cooldowns {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Check every cooldown of the command and record the use if all of them pass
+  pub fn check(&mut self, command: &str, cooldowns: &[Cooldown], source: &CommandSource<'_>) -> Result<(), CooldownHit> {
+    let buckets: Vec<(Cooldown, u64)> = cooldowns.iter().map(|c| (*c, c.bucket.key(source))).collect();
+    self.check_at(command, &buckets, Instant::now())
+  }
+
+  /// Same as [Cooldowns::check], with the id of each bucket already resolved
+  fn check_at(&mut self, command: &str, buckets: &[(Cooldown, u64)], now: Instant) -> Result<(), CooldownHit> {
+    if self.usages.len() > CLEANUP_THRESHOLD { self.cleanup(now); }
+
+    let mut keys = Vec::new();
+    let mut hit: Option<CooldownHit> = None;
+    for (cooldown, id) in buckets {
+      let key = (command.to_string(), cooldown.bucket, *id);
+      let usage = self.usages.entry(key.clone()).or_default();
+      usage.prune(now, cooldown.duration);
+
+      if usage.uses.len() >= cooldown.burst.max(1) {
+        let oldest = *usage.uses.front().expect("burst is at least 1");
+        let remaining = cooldown.duration.saturating_sub(now.duration_since(oldest));
+        let notify = !usage.notified;
+        usage.notified = true;
+        hit = Some(match hit {
+          Some(h) => CooldownHit { remaining: h.remaining.max(remaining), notify: h.notify || notify },
+          None => CooldownHit { remaining, notify }
+        });
+      }
+      keys.push(key);
+    }
+
+    if let Some(hit) = hit { return Err(hit) }
+
+    for key in keys {
+      if let Some(usage) = self.usages.get_mut(&key) {
+        usage.uses.push_back(now);
+        usage.notified = false;
+      }
+    }
+    Ok(())
+  }
+
+  fn cleanup(&mut self, now: Instant) {
+    // no bucket lives longer than a day, anything older than that is expired for sure
+    let max_age = Duration::from_secs(60 * 60 * 24);
+    self.usages.retain(|_, usage| {
+      usage.uses.back().map(|last| now.duration_since(*last) < max_age).unwrap_or(false)
+    });
+  }
+}
+
+/// Human readable duration: `4.2s`, `3m 12s` or `1h 5m`
+pub fn format_remaining(remaining: Duration) -> String {
+  let secs = remaining.as_secs();
+  if secs < 60 {
+    format!("{:.1}s", remaining.as_secs_f32().max(0.1))
+  } else if secs < 60 * 60 {
+    format!("{}m {}s", secs / 60, secs % 60)
+  } else {
+    format!("{}h {}m", secs / 3600, (secs % 3600) / 60)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn secs(start: Instant, secs: u64) -> Instant {
+    start + Duration::from_secs(secs)
+  }
+
+  #[test]
+  fn burst_allows_several_uses() {
+    let (mut cooldowns, start) = (Cooldowns::new(), Instant::now());
+    let buckets = [(Cooldown::user(10).with_burst(3), 1)];
+    for i in 0..3 {
+      assert!(cooldowns.check_at("ping", &buckets, secs(start, i)).is_ok());
+    }
+
+    let hit = cooldowns.check_at("ping", &buckets, secs(start, 4)).unwrap_err();
+    assert_eq!(hit.remaining, Duration::from_secs(6));
+    // the oldest use expires first, the other ones are still counted
+    assert!(cooldowns.check_at("ping", &buckets, secs(start, 10)).is_ok());
+    assert!(cooldowns.check_at("ping", &buckets, secs(start, 10)).is_err());
+  }
+
+  #[test]
+  fn refused_uses_are_not_recorded() {
+    let (mut cooldowns, start) = (Cooldowns::new(), Instant::now());
+    let buckets = [(Cooldown::user(5), 1)];
+    assert!(cooldowns.check_at("ping", &buckets, start).is_ok());
+    for i in 1..5 {
+      assert!(cooldowns.check_at("ping", &buckets, secs(start, i)).is_err());
+    }
+    assert!(cooldowns.check_at("ping", &buckets, secs(start, 5)).is_ok());
+  }
+
+  #[test]
+  fn each_bucket_kind_is_separate() {
+    for cooldown in [Cooldown::user(10), Cooldown::channel(10), Cooldown::guild(10)] {
+      let (mut cooldowns, start) = (Cooldowns::new(), Instant::now());
+      assert!(cooldowns.check_at("ping", &[(cooldown, 1)], start).is_ok());
+      assert!(cooldowns.check_at("ping", &[(cooldown, 1)], start).is_err(), "{}", cooldown.bucket.as_str());
+      // another user, channel or guild is not affected
+      assert!(cooldowns.check_at("ping", &[(cooldown, 2)], start).is_ok(), "{}", cooldown.bucket.as_str());
+      // neither is another command
+      assert!(cooldowns.check_at("help", &[(cooldown, 1)], start).is_ok(), "{}", cooldown.bucket.as_str());
+    }
+
+    // the same id in two kinds of bucket are two buckets
+    let (mut cooldowns, start) = (Cooldowns::new(), Instant::now());
+    assert!(cooldowns.check_at("ping", &[(Cooldown::user(10), 1)], start).is_ok());
+    assert!(cooldowns.check_at("ping", &[(Cooldown::channel(10), 1)], start).is_ok());
+  }
+
+  #[test]
+  fn every_cooldown_must_pass() {
+    let (mut cooldowns, start) = (Cooldowns::new(), Instant::now());
+    let (user, guild) = (Cooldown::user(3), Cooldown::guild(10).with_burst(2));
+    assert!(cooldowns.check_at("prefix", &[(user, 1), (guild, 100)], start).is_ok());
+    assert!(cooldowns.check_at("prefix", &[(user, 2), (guild, 100)], start).is_ok());
+
+    // the guild is full, the longest wait is reported
+    let hit = cooldowns.check_at("prefix", &[(user, 1), (guild, 100)], secs(start, 1)).unwrap_err();
+    assert_eq!(hit.remaining, Duration::from_secs(9));
+    assert!(cooldowns.check_at("prefix", &[(user, 3), (guild, 100)], secs(start, 1)).is_err());
+    // the refused use was not counted for the new user
+    assert!(cooldowns.check_at("prefix", &[(user, 3), (guild, 200)], secs(start, 1)).is_ok());
+  }
+
+  #[test]
+  fn the_user_is_told_once() {
+    let (mut cooldowns, start) = (Cooldowns::new(), Instant::now());
+    let buckets = [(Cooldown::user(5), 1)];
+    assert!(cooldowns.check_at("ping", &buckets, start).is_ok());
+    assert!(cooldowns.check_at("ping", &buckets, secs(start, 1)).unwrap_err().notify);
+    assert!(!cooldowns.check_at("ping", &buckets, secs(start, 2)).unwrap_err().notify);
+    assert!(!cooldowns.check_at("ping", &buckets, secs(start, 3)).unwrap_err().notify);
+
+    // a new accepted use resets the notification
+    assert!(cooldowns.check_at("ping", &buckets, secs(start, 5)).is_ok());
+    assert!(cooldowns.check_at("ping", &buckets, secs(start, 6)).unwrap_err().notify);
+  }
+
+  #[test]
+  fn cleanup_drops_the_expired_buckets() {
+    let (mut cooldowns, start) = (Cooldowns::new(), Instant::now());
+    for id in 0..=CLEANUP_THRESHOLD as u64 {
+      assert!(cooldowns.check_at("ping", &[(Cooldown::user(5), id)], start).is_ok());
+    }
+    let later = start + Duration::from_secs(60 * 60 * 24);
+    assert!(cooldowns.check_at("ping", &[(Cooldown::user(5), 0)], later).is_ok());
+    assert_eq!(cooldowns.usages.len(), 1);
+  }
+
+  #[test]
+  fn remaining_is_human_readable() {
+    assert_eq!(format_remaining(Duration::from_millis(4200)), "4.2s");
+    assert_eq!(format_remaining(Duration::ZERO), "0.1s");
+    assert_eq!(format_remaining(Duration::from_secs(192)), "3m 12s");
+    assert_eq!(format_remaining(Duration::from_secs(3900)), "1h 5m");
+  }
+}
@@ -0,0 +1,184 @@
+use std::{ collections::HashMap, sync::Arc, time::Duration };
+use once_cell::sync::Lazy;
+use serenity::{ async_trait, prelude::{ Context, RwLock, Mutex, TypeMapKey } };
+
+use crate::{ Storage, utils, libs::i18n, maintenance::Maintenance };
+use super::{ checks, commands::{ CommandData, CommandError, CommandInfo }, source::CommandSource };
+
+/// Everything a hook can read about the command being run
+pub struct HookContext<'a> {
+  pub ctx: &'a Context,
+  pub source: &'a CommandSource<'a>,
+  pub storage: &'a Storage,
+  pub command: &'a CommandData,
+  pub info: &'a CommandInfo
+}
+
+pub enum HookFlow {
+  Continue,
+  /// the command is not run, the error is given to the next hooks as the result
+  Stop(CommandError)
+}
+
+/// Code run around every command.
+///
+/// `before` hooks run in registration order once the permission checks passed and before the cooldowns,
+/// `after` hooks run for every command with its result and `on_error` only when it failed.
+#[async_trait]
+pub trait Hook: Send + Sync {
+  fn name(&self) -> &'static str;
+
+  async fn before(&self, _hook: &HookContext<'_>) -> HookFlow {
+    HookFlow::Continue
+  }
+
+  async fn after(&self, _hook: &HookContext<'_>, _elapsed: Duration, _result: &Result<(), CommandError>) {}
+
+  async fn on_error(&self, _hook: &HookContext<'_>, _elapsed: Duration, _error: &CommandError) {}
+}
+
+static HOOKS: Lazy<RwLock<Vec<Arc<dyn Hook>>>> = Lazy::new(|| RwLock::new(Vec::new()));
+
+/// Add a hook at the end of the pipeline
+pub async fn register(hook: impl Hook + 'static) {
+  let mut hooks = HOOKS.write().await;
+  if hooks.iter().any(|h| h.name() == hook.name()) {
+    utils::warn("CommandHooks", format!("A hook named `{}` is already registered", hook.name()).as_str());
+  }
+  hooks.push(Arc::new(hook));
+}
+
+/// Register the hooks of the engine, before the client starts
+pub async fn register_builtins() {
+  register(MaintenanceGate).await;
+  register(ExecutionLog).await;
+  register(UsageCounter).await;
+}
+
+async fn hooks() -> Vec<Arc<dyn Hook>> {
+  HOOKS.read().await.clone()
+}
+
+/// Run the `before` hooks, stops at the first one refusing the command
+pub (in crate::handle) async fn run_before(hook: &HookContext<'_>) -> Result<(), CommandError> {
+  for h in hooks().await {
+    if let HookFlow::Stop(err) = h.before(hook).await {
+      return Err(err)
+    }
+  }
+  Ok(())
+}
+
+pub (in crate::handle) async fn run_after(hook: &HookContext<'_>, elapsed: Duration, result: &Result<(), CommandError>) {
+  for h in hooks().await {
+    h.after(hook, elapsed, result).await;
+    if let Err(err) = result {
+      h.on_error(hook, elapsed, err).await;
+    }
+  }
+}
+
+// ==================================
+// built-in hooks
+
+/// Refuse the commands of everyone but the owners and the whitelist during a maintenance
+struct MaintenanceGate;
+
+#[async_trait]
+impl Hook for MaintenanceGate {
+  fn name(&self) -> &'static str { "MaintenanceGate" }
+
+  async fn before(&self, hook: &HookContext<'_>) -> HookFlow {
+    let maintenance_lock = {
+      let data = hook.ctx.data.read().await;
+      data.get::<Maintenance>().expect("Expected Maintenance in TypeMap.").clone()
+    };
+    let maintenance = maintenance_lock.read().await.clone();
+
+    let author = hook.source.author().id;
+    if checks::is_owner(hook.storage, author)
+      || maintenance.allows(hook.info.name, author.0, hook.source.guild_id().map(|g| g.0)) {
+      return HookFlow::Continue
+    }
+
+    let lang = hook.source.lang();
+    let mut content = i18n::translate(&lang, "maintenance.notice", &[]).await;
+    if let Some(reason) = maintenance.reason {
+      content.push_str(&format!("\n{}", i18n::translate(&lang, "maintenance.reason", &[("reason", reason)]).await));
+    }
+    if let Some(eta) = maintenance.eta {
+      content.push_str(&format!("\n{}", i18n::translate(&lang, "maintenance.eta", &[("timestamp", eta.to_string())]).await));
+    }
+    let _ = hook.source.reply_ephemeral(hook.ctx, content).await;
+
+    HookFlow::Stop(CommandError::Maintenance)
+  }
+}
+
+/// Trace every successful command in dev mode
+struct ExecutionLog;
+
+#[async_trait]
+impl Hook for ExecutionLog {
+  fn name(&self) -> &'static str { "ExecutionLog" }
+
+  async fn after(&self, hook: &HookContext<'_>, elapsed: Duration, result: &Result<(), CommandError>) {
+    if result.is_ok() && hook.storage.handler_state.is_dev() {
+      utils::send(
+        "CommandHandler",
+        format!(
+          "Command \x1b[33m{p}{n}\x1b[0m used by \x1b[35m{a}\x1b[0m \x1b[2m({t}ms)\x1b[0m",
+          p = hook.command.prefix, n = hook.command.name, a = hook.source.author().tag(), t = elapsed.as_millis()
+        ).as_str(),
+        36
+      );
+    }
+  }
+}
+
+/// Commands run since the start of the process
+#[derive(Debug, Default)]
+pub struct CommandUsage {
+  pub total: u64,
+  pub failed: u64,
+  pub by_command: HashMap<&'static str, u64>
+}
+
+impl TypeMapKey for CommandUsage {
+  type Value = Arc<Mutex<CommandUsage>>;
+}
+
+impl CommandUsage {
+  /// The `count` most used commands, most used first
+  pub fn top(&self, count: usize) -> Vec<(&'static str, u64)> {
+    let mut commands: Vec<(&'static str, u64)> = self.by_command.iter().map(|(name, uses)| (*name, *uses)).collect();
+    commands.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    commands.truncate(count);
+    commands
+  }
+}
+
+/// Count the commands which were actually run, the refused ones are ignored
+struct UsageCounter;
+
+#[async_trait]
+impl Hook for UsageCounter {
+  fn name(&self) -> &'static str { "UsageCounter" }
+
+  async fn after(&self, hook: &HookContext<'_>, _elapsed: Duration, result: &Result<(), CommandError>) {
+    let refused = matches!(
+      result,
+      Err(CommandError::NoPermissions | CommandError::Cooldown | CommandError::Maintenance | CommandError::InvalidArgument(_))
+    );
+    if refused { return }
+
+    let usage_lock = {
+      let data = hook.ctx.data.read().await;
+      data.get::<CommandUsage>().expect("Expected CommandUsage in TypeMap.").clone()
+    };
+    let mut usage = usage_lock.lock().await;
+    usage.total += 1;
+    if result.is_err() { usage.failed += 1 }
+    *usage.by_command.entry(hook.info.name).or_insert(0) += 1;
+  }
+}
\ No newline at end of file
@@ -0,0 +1,258 @@
+use std::collections::HashMap;
+use serde_json::Value;
+use serenity::{
+  builder::CreateApplicationCommand,
+  http::Http,
+  model::{
+    Permissions,
+    id::GuildId,
+    application::{
+      command::{ Command as ApplicationCommand, CommandOptionType, CommandType },
+      interaction::application_command::{ ApplicationCommandInteraction, CommandDataOption }
+    }
+  },
+  prelude::Context
+};
+
+use crate::{ Storage, utils };
+use super::{
+  commands::{ self, CommandData, CommandInfo, OptionKind, SlashScope, REGISTRY },
+  source::CommandSource
+};
+
+// ==================================
+// handler
+
+pub async fn execute(ctx: &Context, interaction: &ApplicationCommandInteraction, storage: &Storage) {
+  if interaction.user.bot { return; }
+
+  let registered = match REGISTRY.get(&interaction.data.name) {
+    Some(registered) => registered,
+    None => {
+      utils::warn_with_cause("InteractionHandler", "Unknown application command", interaction.data.name.as_str());
+      return
+    }
+  };
+
+  let command = CommandData {
+    name: interaction.data.name.clone(),
+    prefix: "/".to_string(),
+    raw_args: options_to_args(&registered.info, &interaction.data.options)
+  };
+
+  let source = CommandSource::from_interaction(interaction);
+  commands::exec_command(ctx, &source, storage, command).await;
+}
+
+/// Write the options of the interaction as prefix arguments, so the command parses both the same way
+fn options_to_args(info: &CommandInfo, options: &[CommandDataOption]) -> String {
+  let mut args: Vec<String> = Vec::new();
+
+  for declared in info.options.iter() {
+    let value = match options.iter().find(|o| o.name == declared.name).and_then(|o| o.value.as_ref()) {
+      Some(value) => value,
+      None => continue
+    };
+
+    match declared.kind {
+      OptionKind::Flag => {
+        if value.as_bool().unwrap_or(false) { args.push(format!("--{}", declared.name)); }
+      }
+      OptionKind::String => {
+        if let Some(text) = value.as_str() { args.push(quote(text)); }
+      }
+    }
+  }
+
+  args.join(" ")
+}
+
+fn quote(value: &str) -> String {
+  if value.is_empty() || value.contains(char::is_whitespace) || value.starts_with('"') || value.starts_with('-') {
+    format!("\"{}\"", value.replace('"', "\\\""))
+  } else {
+    value.to_string()
+  }
+}
+
+// ==================================
+// registration
+
+fn option_type(kind: OptionKind) -> CommandOptionType {
+  match kind {
+    OptionKind::String => CommandOptionType::String,
+    OptionKind::Flag => CommandOptionType::Boolean
+  }
+}
+
+fn build<'a>(command: &'a mut CreateApplicationCommand, info: &CommandInfo) -> &'a mut CreateApplicationCommand {
+  command.name(info.name).description(info.description).kind(CommandType::ChatInput);
+  // an edit keeps every field left out, so the ones the registry does not use are cleared
+  if info.permissions.is_empty() {
+    command.0.insert("default_member_permissions", Value::Null);
+  } else {
+    command.default_member_permissions(info.permissions);
+  }
+  command.dm_permission(!info.guild_only);
+  command.0.insert("name_localizations", Value::Null);
+  command.0.insert("description_localizations", Value::Null);
+
+  for option in info.options.iter() {
+    command.create_option(|o| {
+      o.name(option.name)
+        .description(option.description)
+        .kind(option_type(option.kind))
+        .required(option.required);
+      for choice in option.choices.iter() {
+        o.add_string_choice(*choice, *choice);
+      }
+      o
+    });
+  }
+  command
+}
+
+/// Whether the command registered on Discord still matches the registry
+fn is_up_to_date(existing: &ApplicationCommand, info: &CommandInfo) -> bool {
+  existing.kind == CommandType::ChatInput
+    && existing.description == info.description
+    && existing.default_member_permissions.unwrap_or(Permissions::empty()) == info.permissions
+    // Discord leaves the field out when the command is usable in DMs
+    && existing.dm_permission.unwrap_or(true) != info.guild_only
+    && no_localizations(&existing.name_localizations)
+    && no_localizations(&existing.description_localizations)
+    && existing.options.len() == info.options.len()
+    && existing.options.iter().zip(info.options.iter()).all(|(e, o)| {
+      e.name == o.name
+        && e.description == o.description
+        && no_localizations(&e.name_localizations)
+        && no_localizations(&e.description_localizations)
+        && e.kind == option_type(o.kind)
+        && e.required == o.required
+        && e.choices.len() == o.choices.len()
+        // choices are registered with the same name and value, see [build]
+        && e.choices.iter().zip(o.choices.iter()).all(|(c, wanted)| c.name == *wanted && c.value.as_str() == Some(*wanted))
+    })
+}
+
+/// The registry has no translations, the ones added elsewhere are overwritten
+fn no_localizations(localizations: &Option<HashMap<String, String>>) -> bool {
+  localizations.as_ref().is_none_or(|l| l.is_empty())
+}
+
+#[derive(Debug, Default)]
+struct SyncReport {
+  created: usize,
+  updated: usize,
+  deleted: usize,
+  failed: usize
+}
+
+/// Create, update and delete the application commands of one scope so they match the registry
+async fn sync_scope(http: &Http, guild_id: Option<GuildId>, wanted: &[&CommandInfo]) -> Result<SyncReport, String> {
+  let existing = match guild_id {
+    Some(guild_id) => guild_id.get_application_commands(http).await,
+    None => ApplicationCommand::get_global_application_commands(http).await
+  }.map_err(|e| e.to_string())?;
+
+  let mut report = SyncReport::default();
+
+  for info in wanted.iter() {
+    let result = match existing.iter().find(|e| e.name == info.name) {
+      Some(current) if is_up_to_date(current, info) => continue,
+      Some(current) => {
+        report.updated += 1;
+        match guild_id {
+          Some(guild_id) => guild_id.edit_application_command(http, current.id, |c| build(c, info)).await,
+          None => ApplicationCommand::edit_global_application_command(http, current.id, |c| build(c, info)).await
+        }
+      }
+      None => {
+        report.created += 1;
+        match guild_id {
+          Some(guild_id) => guild_id.create_application_command(http, |c| build(c, info)).await,
+          None => ApplicationCommand::create_global_application_command(http, |c| build(c, info)).await
+        }
+      }
+    };
+    if let Err(why) = result {
+      report.failed += 1;
+      utils::error("SlashCommands", format!("Cannot register the command `{}`", info.name).as_str(), why.to_string().as_str());
+    }
+  }
+
+  for stale in existing.iter().filter(|e| !wanted.iter().any(|w| w.name == e.name)) {
+    let result = match guild_id {
+      Some(guild_id) => guild_id.delete_application_command(http, stale.id).await,
+      None => ApplicationCommand::delete_global_application_command(http, stale.id).await
+    };
+    match result {
+      Ok(()) => report.deleted += 1,
+      Err(why) => {
+        report.failed += 1;
+        utils::error("SlashCommands", format!("Cannot delete the command `{}`", stale.name).as_str(), why.to_string().as_str());
+      }
+    }
+  }
+
+  Ok(report)
+}
+
+/// Diff the registry against the commands known by Discord and apply the changes, called at `ready`
+pub async fn sync(http: &Http, guilds: &[GuildId]) {
+  let global = REGISTRY.list().iter()
+    .filter(|c| c.info.slash == SlashScope::Global)
+    .map(|c| &c.info)
+    .collect::<Vec<&CommandInfo>>();
+  let guild_only = REGISTRY.list().iter()
+    .filter(|c| c.info.slash == SlashScope::Guild)
+    .map(|c| &c.info)
+    .collect::<Vec<&CommandInfo>>();
+
+  let scopes = std::iter::once((None, &global))
+    .chain(guilds.iter().map(|g| (Some(*g), &guild_only)));
+
+  for (guild_id, wanted) in scopes {
+    let scope_name = guild_id.map(|g| format!("guild {}", g.0)).unwrap_or("global".to_string());
+    match sync_scope(http, guild_id, wanted).await {
+      Ok(report) => {
+        if report.created + report.updated + report.deleted + report.failed > 0 {
+          utils::info_with_detail(
+            "SlashCommands",
+            format!("Application commands synchronized ({scope_name})").as_str(),
+            format!("{} created, {} updated, {} deleted, {} failed", report.created, report.updated, report.deleted, report.failed).as_str()
+          );
+        }
+      }
+      Err(why) => utils::error("SlashCommands", format!("Cannot synchronize the application commands ({scope_name})").as_str(), why.as_str())
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  fn registered(fields: Value) -> ApplicationCommand {
+    let mut command = json!({ "id": "1", "type": 1, "application_id": "2", "name": "prefix", "description": "Préfixes", "version": "3" });
+    command.as_object_mut().unwrap().extend(fields.as_object().unwrap().clone());
+    serde_json::from_value(command).unwrap()
+  }
+
+  #[test]
+  fn permissions_context_and_localizations_are_compared() {
+    let info = CommandInfo { name: "prefix", description: "Préfixes", ..Default::default() };
+    assert!(is_up_to_date(&registered(json!({})), &info));
+    assert!(is_up_to_date(&registered(json!({ "dm_permission": true, "name_localizations": {} })), &info));
+    assert!(!is_up_to_date(&registered(json!({ "type": 2 })), &info));
+    assert!(!is_up_to_date(&registered(json!({ "dm_permission": false })), &info));
+    assert!(!is_up_to_date(&registered(json!({ "default_member_permissions": "32" })), &info));
+    assert!(!is_up_to_date(&registered(json!({ "name_localizations": { "en-US": "prefix" } })), &info));
+
+    let guarded = CommandInfo { permissions: Permissions::MANAGE_GUILD, guild_only: true, ..info };
+    assert!(!is_up_to_date(&registered(json!({})), &guarded));
+    assert!(is_up_to_date(&registered(json!({ "default_member_permissions": "32", "dm_permission": false })), &guarded));
+  }
+}
@@ -1 +1,8 @@
-pub mod commands;
\ No newline at end of file
+pub mod commands;
+pub mod args;
+pub mod prefixes;
+pub mod cooldowns;
+pub mod checks;
+pub mod source;
+pub mod interactions;
+pub mod hooks;
\ No newline at end of file
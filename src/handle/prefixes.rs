@@ -0,0 +1,127 @@
+use std::{sync::Arc, collections::HashMap};
+use serenity::{
+  model::{ prelude::Message, id::{ GuildId, UserId } },
+  prelude::{ TypeMapKey, RwLock }
+};
+use sqlx::{ MySqlConnection, Row };
+
+use crate::utils;
+
+pub const MAX_PREFIXES_PER_GUILD: usize = 5;
+pub const MAX_PREFIX_LEN: usize = 10;
+
+#[derive(Debug)]
+pub enum PrefixError {
+  TooMany,
+  TooLong,
+  Empty,
+  AlreadyExists,
+  NotFound,
+  Database(String)
+}
+
+/// In-memory copy of the `guild_prefixes` table, so resolving a prefix never hits the database
+#[derive(Debug, Default)]
+pub struct GuildPrefixes {
+  prefixes: HashMap<u64, Vec<String>>
+}
+
+impl TypeMapKey for GuildPrefixes {
+  type Value = Arc<RwLock<GuildPrefixes>>;
+}
+
+impl GuildPrefixes {
+  /// Load every custom prefix, the table is created by the migrations
+  pub async fn load(conn: &mut MySqlConnection) -> Result<Self, String> {
+    let rows = sqlx::query("SELECT guild_id, prefix FROM guild_prefixes;")
+      .fetch_all(&mut *conn)
+      .await
+      .map_err(|e| e.to_string())?;
+
+    let mut prefixes: HashMap<u64, Vec<String>> = HashMap::new();
+    for row in rows {
+      let guild_id: u64 = row.try_get("guild_id").map_err(|e| e.to_string())?;
+      let prefix: String = row.try_get("prefix").map_err(|e| e.to_string())?;
+      prefixes.entry(guild_id).or_default().push(prefix);
+    }
+
+    utils::info("GuildPrefixes", format!("Custom prefixes loaded for {} guild.s", prefixes.len()).as_str());
+    Ok(GuildPrefixes { prefixes })
+  }
+
+  pub fn get(&self, guild_id: GuildId) -> Option<&Vec<String>> {
+    self.prefixes.get(&guild_id.0).filter(|p| !p.is_empty())
+  }
+
+  pub async fn add(&mut self, conn: &mut MySqlConnection, guild_id: GuildId, prefix: &str) -> Result<(), PrefixError> {
+    let prefix = prefix.trim();
+    if prefix.is_empty() { return Err(PrefixError::Empty) }
+    if prefix.chars().count() > MAX_PREFIX_LEN { return Err(PrefixError::TooLong) }
+
+    let current = self.prefixes.entry(guild_id.0).or_default();
+    if current.iter().any(|p| p == prefix) { return Err(PrefixError::AlreadyExists) }
+    if current.len() >= MAX_PREFIXES_PER_GUILD { return Err(PrefixError::TooMany) }
+
+    sqlx::query("INSERT INTO guild_prefixes (guild_id, prefix) VALUES (?, ?);")
+      .bind(guild_id.0)
+      .bind(prefix)
+      .execute(conn)
+      .await
+      .map_err(|e| PrefixError::Database(e.to_string()))?;
+
+    current.push(prefix.to_string());
+    Ok(())
+  }
+
+  pub async fn remove(&mut self, conn: &mut MySqlConnection, guild_id: GuildId, prefix: &str) -> Result<(), PrefixError> {
+    let prefix = prefix.trim();
+    let current = self.prefixes.entry(guild_id.0).or_default();
+    if !current.iter().any(|p| p == prefix) { return Err(PrefixError::NotFound) }
+
+    sqlx::query("DELETE FROM guild_prefixes WHERE guild_id = ? AND prefix = ?;")
+      .bind(guild_id.0)
+      .bind(prefix)
+      .execute(conn)
+      .await
+      .map_err(|e| PrefixError::Database(e.to_string()))?;
+
+    current.retain(|p| p != prefix);
+    Ok(())
+  }
+
+  /// Remove every custom prefix of the guild, the default prefix applies again
+  pub async fn reset(&mut self, conn: &mut MySqlConnection, guild_id: GuildId) -> Result<(), PrefixError> {
+    sqlx::query("DELETE FROM guild_prefixes WHERE guild_id = ?;")
+      .bind(guild_id.0)
+      .execute(conn)
+      .await
+      .map_err(|e| PrefixError::Database(e.to_string()))?;
+
+    self.prefixes.remove(&guild_id.0);
+    Ok(())
+  }
+}
+
+/// Find which prefix the message starts with.
+///
+/// The bot mention is always accepted, then the custom prefixes of the guild (longest first)
+/// or the default prefix when the guild has none.
+pub fn resolve(message: &Message, prefixes: Option<&Vec<String>>, default: &str, bot_id: Option<UserId>) -> Option<String> {
+  let content = message.content.as_str();
+
+  if let Some(id) = bot_id {
+    let mentions = [format!("<@{}>", id.0), format!("<@!{}>", id.0)];
+    if let Some(mention) = mentions.into_iter().find(|m| content.starts_with(m.as_str())) {
+      return Some(mention);
+    }
+  }
+
+  match prefixes {
+    Some(custom) => {
+      let mut sorted = custom.iter().collect::<Vec<&String>>();
+      sorted.sort_by_key(|p| std::cmp::Reverse(p.len()));
+      sorted.into_iter().find(|p| content.starts_with(p.as_str())).cloned()
+    }
+    None => if content.starts_with(default) { Some(default.to_string()) } else { None }
+  }
+}
\ No newline at end of file
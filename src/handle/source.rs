@@ -0,0 +1,179 @@
+use std::{ collections::HashMap, sync::{ RwLock, atomic::{ AtomicBool, AtomicU64, Ordering } } };
+use once_cell::sync::Lazy;
+use serenity::{
+  builder::CreateEmbed,
+  model::{
+    prelude::Message,
+    user::User,
+    id::{ ChannelId, GuildId },
+    application::interaction::{ InteractionResponseType, MessageFlags, application_command::ApplicationCommandInteraction }
+  },
+  prelude::Context
+};
+
+use crate::{ utils, libs::i18n };
+use super::commands::CommandError;
+
+/// Forget every known language past this size, they are learned again from the next interactions
+const MAX_KNOWN_LANGS: usize = 10_000;
+
+/// Languages seen in the interactions, a message does not carry the language of its author
+#[derive(Default)]
+struct KnownLangs {
+  users: HashMap<u64, String>,
+  guilds: HashMap<u64, String>
+}
+
+static KNOWN_LANGS: Lazy<RwLock<KnownLangs>> = Lazy::new(|| RwLock::new(KnownLangs::default()));
+
+pub enum SourceKind<'a> {
+  Message(&'a Message),
+  Interaction(&'a ApplicationCommandInteraction)
+}
+
+/// Where a command comes from: a prefixed message or a slash command.
+///
+/// Commands only talk to the user through this type so they work the same way in both cases.
+pub struct CommandSource<'a> {
+  pub kind: SourceKind<'a>,
+  /// an interaction can only be answered once, the next replies are follow-ups
+  responded: AtomicBool,
+  /// id of the last reply to a message, 0 before the first one
+  last_reply: AtomicU64
+}
+
+impl<'a> CommandSource<'a> {
+  pub fn from_message(message: &'a Message) -> Self {
+    CommandSource { kind: SourceKind::Message(message), responded: AtomicBool::new(false), last_reply: AtomicU64::new(0) }
+  }
+
+  pub fn from_interaction(interaction: &'a ApplicationCommandInteraction) -> Self {
+    let mut known = KNOWN_LANGS.write().unwrap_or_else(|e| e.into_inner());
+    if known.users.len() + known.guilds.len() > MAX_KNOWN_LANGS { *known = KnownLangs::default(); }
+    known.users.insert(interaction.user.id.0, lang_of(&interaction.locale));
+    if let (Some(guild_id), Some(locale)) = (interaction.guild_id, &interaction.guild_locale) {
+      known.guilds.insert(guild_id.0, lang_of(locale));
+    }
+    drop(known);
+
+    CommandSource { kind: SourceKind::Interaction(interaction), responded: AtomicBool::new(false), last_reply: AtomicU64::new(0) }
+  }
+
+  pub fn author(&self) -> &User {
+    match self.kind {
+      SourceKind::Message(message) => &message.author,
+      SourceKind::Interaction(interaction) => &interaction.user
+    }
+  }
+
+  pub fn guild_id(&self) -> Option<GuildId> {
+    match self.kind {
+      SourceKind::Message(message) => message.guild_id,
+      SourceKind::Interaction(interaction) => interaction.guild_id
+    }
+  }
+
+  pub fn channel_id(&self) -> ChannelId {
+    match self.kind {
+      SourceKind::Message(message) => message.channel_id,
+      SourceKind::Interaction(interaction) => interaction.channel_id
+    }
+  }
+
+  pub fn is_interaction(&self) -> bool {
+    matches!(self.kind, SourceKind::Interaction(_))
+  }
+
+  /// Language of the user (`fr`, `en`...).
+  /// For a message, the language of the last interaction of the author or of the guild, [i18n::DEFAULT_LANG] otherwise
+  pub fn lang(&self) -> String {
+    match self.kind {
+      SourceKind::Message(message) => {
+        let known = KNOWN_LANGS.read().unwrap_or_else(|e| e.into_inner());
+        known.users.get(&message.author.id.0)
+          .or_else(|| message.guild_id.and_then(|id| known.guilds.get(&id.0)))
+          .cloned()
+          .unwrap_or(i18n::DEFAULT_LANG.to_string())
+      }
+      SourceKind::Interaction(interaction) => lang_of(&interaction.locale)
+    }
+  }
+
+  pub async fn reply(&self, ctx: &Context, content: impl Into<String>) -> Result<(), CommandError> {
+    self.send(ctx, content.into(), false).await
+  }
+
+  /// Same as [CommandSource::reply], only visible by the user for interactions
+  pub async fn reply_ephemeral(&self, ctx: &Context, content: impl Into<String>) -> Result<(), CommandError> {
+    self.send(ctx, content.into(), true).await
+  }
+
+  async fn send(&self, ctx: &Context, content: String, ephemeral: bool) -> Result<(), CommandError> {
+    let result = match self.kind {
+      SourceKind::Message(message) => message.reply(ctx, content).await.map(|m| self.last_reply.store(m.id.0, Ordering::SeqCst)),
+      SourceKind::Interaction(interaction) => {
+        let flags = if ephemeral { MessageFlags::EPHEMERAL } else { MessageFlags::empty() };
+        if self.responded.swap(true, Ordering::SeqCst) {
+          interaction.create_followup_message(ctx, |m| m.content(content).flags(flags)).await.map(|_| ())
+        } else {
+          interaction.create_interaction_response(ctx, |r| {
+            r.kind(InteractionResponseType::ChannelMessageWithSource)
+              .interaction_response_data(|d| d.content(content).flags(flags))
+          }).await
+        }
+      }
+    };
+
+    sent(result)
+  }
+
+  pub async fn reply_embed(&self, ctx: &Context, embed: CreateEmbed) -> Result<(), CommandError> {
+    let result = match self.kind {
+      SourceKind::Message(message) => {
+        message.channel_id.send_message(ctx, |m| m.reference_message(message).set_embed(embed)).await
+          .map(|m| self.last_reply.store(m.id.0, Ordering::SeqCst))
+      }
+      SourceKind::Interaction(interaction) => {
+        if self.responded.swap(true, Ordering::SeqCst) {
+          interaction.create_followup_message(ctx, |m| m.add_embed(embed)).await.map(|_| ())
+        } else {
+          interaction.create_interaction_response(ctx, |r| {
+            r.kind(InteractionResponseType::ChannelMessageWithSource)
+              .interaction_response_data(|d| d.add_embed(embed))
+          }).await
+        }
+      }
+    };
+
+    sent(result)
+  }
+
+  /// Replace the last reply by an embed, the original response for an interaction.
+  /// Sends a new reply when nothing was answered yet
+  pub async fn edit_reply_embed(&self, ctx: &Context, embed: CreateEmbed) -> Result<(), CommandError> {
+    let result = match self.kind {
+      SourceKind::Message(message) => match self.last_reply.load(Ordering::SeqCst) {
+        0 => return self.reply_embed(ctx, embed).await,
+        id => message.channel_id.edit_message(ctx, id, |m| m.content("").set_embed(embed)).await.map(|_| ())
+      },
+      SourceKind::Interaction(interaction) => {
+        if !self.responded.load(Ordering::SeqCst) { return self.reply_embed(ctx, embed).await }
+        interaction.edit_original_interaction_response(ctx, |r| r.content("").set_embed(embed)).await.map(|_| ())
+      }
+    };
+
+    sent(result)
+  }
+}
+
+/// `en-US` → `en`
+fn lang_of(locale: &str) -> String {
+  locale.split('-').next().filter(|l| !l.is_empty()).unwrap_or(i18n::DEFAULT_LANG).to_lowercase()
+}
+
+fn sent(result: serenity::Result<()>) -> Result<(), CommandError> {
+  result.map_err(|why| {
+    utils::error("MessageSender", "An error occured while sending the message", why.to_string().as_str());
+    CommandError::MessageNotSent
+  })
+}
\ No newline at end of file
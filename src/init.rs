@@ -1,80 +1,775 @@
 use std::{
-  fs::OpenOptions,
-  io::Read
+  collections::BTreeMap,
+  env,
+  fs,
+  fmt::{ self, Display, Formatter },
+  io::ErrorKind,
+  path::PathBuf
 };
-use serde::Deserialize;
+use serde::{ Deserialize, Serialize };
 
-use crate::utils;
+use crate::{ utils, status, logging };
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct Config {
   pub client: Client,
   pub params: Params,
   pub security: Security,
-  pub i18n: I18n
+  pub i18n: I18n,
+  #[serde(default)]
+  pub latency: LatencyConfig,
+  #[serde(default)]
+  pub watchdog: WatchdogConfig,
+  #[serde(default)]
+  pub logging: LoggingConfig
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct Client {
   pub version: String,
   pub build_type: String,
+  #[serde(default)]
   pub dev: bool
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct Params {
+  #[serde(default)]
   pub status: Vec<Status>,
+  #[serde(default = "default_auto_status")]
   pub auto_status: bool,
   pub prefix: String,
-  pub status_time: i32
+  /// seconds between two statuses
+  #[serde(default = "default_status_time")]
+  pub status_time: i32,
+  /// sequential, random or weighted
+  #[serde(default = "default_status_mode")]
+  pub status_mode: String,
+  #[serde(default = "default_case_insensitive_commands")]
+  pub case_insensitive_commands: bool,
+  /// user ids allowed to run owner-only commands, the application owners are always added
+  #[serde(default)]
+  pub owners: Vec<u64>,
+  /// guilds where the guild-scoped slash commands are registered
+  #[serde(default)]
+  pub slash_guilds: Vec<u64>,
+  /// shown instead of the rotation when the instance is in dev mode
+  #[serde(default = "default_dev_status")]
+  pub dev_status: Status,
+  #[serde(default = "default_maintenance_status")]
+  pub maintenance_status: Status,
+  #[serde(default = "default_debug_status")]
+  pub debug_status: Status,
+  /// url used by the STREAMING statuses
+  #[serde(default = "default_streaming_url")]
+  pub streaming_url: String
 }
 
-#[derive(Deserialize, Debug, Clone)]
+fn default_auto_status() -> bool { true }
+
+fn default_status_time() -> i32 { 120 }
+
+fn default_case_insensitive_commands() -> bool { true }
+
+fn default_status_mode() -> String { "sequential".to_string() }
+
+fn default_dev_status() -> Status { watching_status("⚙️ Mode développeur") }
+
+fn default_maintenance_status() -> Status { watching_status("🚧 Mode maintenance") }
+
+fn default_debug_status() -> Status { watching_status("🔧 Mode debug") }
+
+fn watching_status(message: &str) -> Status {
+  Status {
+    status_type: "WATCHING".to_string(), message: message.to_string(), weight: 1,
+    online_status: None, from: None, until: None, hours: None
+  }
+}
+
+fn default_streaming_url() -> String { "https://www.twitch.tv/sedorriku_".to_string() }
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Status {
   pub status_type: String,
-  pub message: String
+  pub message: String,
+  /// chance to be picked by the weighted rotation
+  #[serde(default = "default_status_weight")]
+  pub weight: u32,
+  /// online, idle, dnd or invisible
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub online_status: Option<String>,
+  /// `MM-DD` dates and `HH-HH` hours during which the status is shown
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub from: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub until: Option<String>,
+  #[serde(default, skip_serializing_if = "Option::is_none")]
+  pub hours: Option<String>
 }
 
-#[derive(Deserialize)]
+fn default_status_weight() -> u32 { 1 }
+
+#[derive(Serialize, Deserialize)]
 pub struct Security {
+  #[serde(default)]
   pub rewrite_archive_if_invalid: bool,
+  #[serde(default = "default_auto_save_archive")]
   pub auto_save_archive: bool
 }
 
-#[derive(Deserialize)]
+fn default_auto_save_archive() -> bool { true }
+
+#[derive(Serialize, Deserialize)]
 pub struct I18n {
   pub locales_dir: String
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+pub struct LatencyConfig {
+  /// seconds between two samples
+  #[serde(default = "default_latency_interval")]
+  pub interval: u64,
+  /// samples kept per shard
+  #[serde(default = "default_latency_history")]
+  pub history: usize,
+  /// a shard above this latency for `consecutive` samples is degraded
+  #[serde(default = "default_latency_warn_ms")]
+  pub warn_ms: u64,
+  /// a degraded shard recovers under this latency
+  #[serde(default = "default_latency_recover_ms")]
+  pub recover_ms: u64,
+  #[serde(default = "default_latency_consecutive")]
+  pub consecutive: usize,
+  /// log, webhook and/or file
+  #[serde(default = "default_latency_alert_sinks")]
+  pub alert_sinks: Vec<String>,
+  #[serde(default)]
+  pub webhook_url: Option<String>,
+  #[serde(default = "default_latency_alert_file")]
+  pub alert_file: String
+}
+
+impl Default for LatencyConfig {
+  fn default() -> Self {
+    LatencyConfig {
+      interval: default_latency_interval(),
+      history: default_latency_history(),
+      warn_ms: default_latency_warn_ms(),
+      recover_ms: default_latency_recover_ms(),
+      consecutive: default_latency_consecutive(),
+      alert_sinks: default_latency_alert_sinks(),
+      webhook_url: None,
+      alert_file: default_latency_alert_file()
+    }
+  }
+}
+
+fn default_latency_interval() -> u64 { 10 }
+fn default_latency_history() -> usize { 60 }
+fn default_latency_warn_ms() -> u64 { 500 }
+fn default_latency_recover_ms() -> u64 { 300 }
+fn default_latency_consecutive() -> usize { 3 }
+fn default_latency_alert_sinks() -> Vec<String> { vec!["log".to_string()] }
+fn default_latency_alert_file() -> String { "./latency_alerts.log".to_string() }
+
+/// Limits in seconds after which a shard is restarted, checked at each latency sample
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct WatchdogConfig {
+  pub enabled: bool,
+  pub no_ack_after: u64,
+  pub stale_after: u64,
+  pub disconnected_after: u64,
+  /// disconnections within `disconnect_window` before a restart
+  pub max_disconnects: usize,
+  pub disconnect_window: u64,
+  /// time given to a restarted shard before it can be restarted again
+  pub restart_cooldown: u64
+}
+
+impl Default for WatchdogConfig {
+  fn default() -> Self {
+    WatchdogConfig {
+      enabled: true,
+      no_ack_after: 120,
+      stale_after: 300,
+      disconnected_after: 120,
+      max_disconnects: 5,
+      disconnect_window: 600,
+      restart_cooldown: 300
+    }
+  }
+}
+
+
+/// Log files, the console output is configured from the command line
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct LoggingConfig {
+  pub enabled: bool,
+  pub dir: String,
+  /// everything but the security messages
+  pub file: String,
+  /// messages of `utils::security` and `security_with_detail`
+  pub security_file: String,
+  /// a file is rotated above this size, 0 to disable
+  pub max_size_mb: u64,
+  /// a file is rotated when the day changes
+  pub daily: bool,
+  /// gzip the rotated files
+  pub compress: bool,
+  /// rotated files kept per stream, 0 to keep them all
+  pub keep_files: usize,
+  /// rotated files older than this are removed, 0 to keep them all
+  pub max_age_days: u64,
+  /// plain or json
+  pub format: String
+}
+
+impl Default for LoggingConfig {
+  fn default() -> Self {
+    LoggingConfig {
+      enabled: false,
+      dir: "./logs".to_string(),
+      file: "mio.log".to_string(),
+      security_file: "security.log".to_string(),
+      max_size_mb: 10,
+      daily: true,
+      compress: true,
+      keep_files: 14,
+      max_age_days: 30,
+      format: "plain".to_string()
+    }
+  }
+}
+
+/// Problem found in the config, all of them are reported at once
+#[derive(Debug, Clone)]
+pub struct ConfigProblem {
+  pub key: String,
+  pub message: String,
+  /// `file:line`, environment variable or CLI flag which gave the value, when it is known
+  pub origin: Option<String>
+}
+
+impl ConfigProblem {
+  fn new(key: &str, message: impl Into<String>) -> ConfigProblem {
+    ConfigProblem { key: key.to_string(), message: message.into(), origin: None }
+  }
+
+  fn at(mut self, origin: Option<String>) -> ConfigProblem {
+    self.origin = origin;
+    self
+  }
+}
+
+impl Display for ConfigProblem {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    match &self.origin {
+      Some(origin) => write!(f, "{origin}: {}: {}", self.key, self.message),
+      None => write!(f, "{}: {}", self.key, self.message)
+    }
+  }
+}
+
+/// Where the value of a key comes from, the later layers win
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigSource {
+  Default,
+  File(String),
+  Env(String),
+  Cli
+}
+
+impl ConfigSource {
+  pub fn describe(&self) -> String {
+    match self {
+      ConfigSource::Default => "default".to_string(),
+      ConfigSource::File(path) => path.clone(),
+      ConfigSource::Env(name) => format!("env {name}"),
+      ConfigSource::Cli => "--set".to_string()
+    }
+  }
+}
+
+/// How the config is found, from the command line. The `MIO_*` variables are read by [load_config]
+#[derive(Debug, Clone, Default)]
+pub struct ConfigOptions {
+  pub path: Option<String>,
+  /// `dev` or `prod`, selects the overlay file
+  pub env: Option<String>,
+  /// `section.key=value` given with `--set`
+  pub overrides: Vec<(String, String)>
+}
+
+impl ConfigOptions {
+  /// `--config`, then `MIO_CONFIG`, then [RELATIVE_CONFIG_DIR]
+  pub fn path(&self) -> String {
+    self.path.clone()
+      .or_else(|| env::var("MIO_CONFIG").ok())
+      .unwrap_or_else(|| RELATIVE_CONFIG_DIR.to_string())
+  }
+
+  /// `--dev`/`--prod`, then `MIO_ENV`
+  pub fn env(&self) -> Option<String> {
+    self.env.clone().or_else(|| env::var("MIO_ENV").ok()).map(|e| e.to_lowercase())
+  }
+
+  /// `./config.toml` with the `dev` env is overlaid by `./config.dev.toml`
+  pub fn overlay_path(&self) -> Option<String> {
+    let env = self.env()?;
+    let path = PathBuf::from(self.path());
+    let stem = path.file_stem()?.to_str()?.to_string();
+    Some(path.with_file_name(format!("{stem}.{env}.toml")).to_string_lossy().to_string())
+  }
+
+  /// Files read by [load_config], the overlay may not exist
+  pub fn files(&self) -> Vec<String> {
+    let mut files = vec![self.path()];
+    files.extend(self.overlay_path());
+    files
+  }
+}
+
+/// Config merged from every layer, with the origin of each value
+pub struct LoadedConfig {
+  pub config: Config,
+  /// given values flattened as `section.key`, the defaults are not included
+  pub values: BTreeMap<String, toml::Value>,
+  origins: Origins
+}
+
+impl LoadedConfig {
+  pub fn source_of(&self, key: &str) -> ConfigSource {
+    self.origins.source_of(key)
+  }
+
+  /// Effective values with their source, defaults included and secrets redacted
+  pub fn describe(&self) -> Vec<(String, String, ConfigSource)> {
+    let mut effective = BTreeMap::new();
+    if let Ok(value) = toml::Value::try_from(&self.config) { flatten(String::new(), value, &mut effective) }
+    effective.into_iter()
+      .map(|(key, value)| {
+        let shown = if is_secret(&key) { "<redacted>".to_string() } else { value.to_string() };
+        let source = self.source_of(&key);
+        (key, shown, source)
+      })
+      .collect()
+  }
+}
+
+/// Layer which gave each key, and the content of the files read to find the line of a key
+struct Origins {
+  sources: BTreeMap<String, ConfigSource>,
+  files: Vec<(String, String)>
+}
+
+impl Origins {
+  fn source_of(&self, key: &str) -> ConfigSource {
+    let mut key = key;
+    loop {
+      if let Some(source) = self.sources.get(key) { return source.clone() }
+      match key.rfind(['.', '[']) {
+        Some(i) => key = &key[..i],
+        None => return ConfigSource::Default
+      }
+    }
+  }
+
+  /// `file:line` or the variable which gave `key`
+  fn locate(&self, key: &str) -> Option<String> {
+    match self.source_of(key) {
+      ConfigSource::Default => None,
+      ConfigSource::Env(name) => Some(format!("env {name}")),
+      ConfigSource::Cli => Some(format!("--set {key}")),
+      ConfigSource::File(path) => {
+        let content = &self.files.iter().find(|(p, _)| *p == path)?.1;
+        let line = match key.split_once('[') {
+          Some((array, rest)) => {
+            let index = rest.trim_end_matches(']').parse::<usize>().ok()?;
+            let (section, name) = array.split_once('.')?;
+            line_of_entry(content, section, name, index)
+          }
+          None => match key.split_once('.') {
+            Some((section, name)) => line_of(content, section, name),
+            None => line_of(content, "", &format!("[{key}]"))
+          }
+        };
+        Some(match line { Some(line) => format!("{path}:{line}"), None => path })
+      }
+    }
+  }
+}
+
+/// Sections and keys without a default value
+const REQUIRED_SECTIONS: [&str; 4] = ["client", "params", "security", "i18n"];
+const REQUIRED_KEYS: [(&str, &str); 4] = [
+  ("client", "version"),
+  ("client", "build_type"),
+  ("params", "prefix"),
+  ("i18n", "locales_dir")
+];
+
+/// Keys never shown by `config show`
+fn is_secret(key: &str) -> bool {
+  let name = key.rsplit('.').next().unwrap_or(key);
+  ["token", "password", "secret", "webhook_url"].iter().any(|s| name.contains(s)) || name.ends_with("_key")
+}
+
+/// Merge the config file, its overlay, the `MIO_SECTION__KEY` variables and the `--set` flags, then validate the result
+pub fn load_config(options: &ConfigOptions) -> Result<LoadedConfig, Vec<ConfigProblem>> {
+  load_config_with(options, env::vars())
+}
+
+/// [load_config] reading the variables from `vars` instead of the environment of the process
+fn load_config_with(options: &ConfigOptions, vars: impl Iterator<Item = (String, String)>) -> Result<LoadedConfig, Vec<ConfigProblem>> {
+  let mut merged = toml::Value::Table(Default::default());
+  let mut sources = BTreeMap::new();
+  let mut files = Vec::new();
+  let mut problems = Vec::new();
+
+  for (i, path) in options.files().into_iter().enumerate() {
+    let content = match fs::read_to_string(&path) {
+      Ok(content) => content,
+      Err(err) if err.kind() == ErrorKind::NotFound && i > 0 => continue,
+      Err(err) if err.kind() == ErrorKind::NotFound => {
+        problems.push(ConfigProblem::new("file", format!("{path} does not exist")));
+        continue;
+      }
+      Err(err) => {
+        problems.push(ConfigProblem::new("file", format!("cannot read {path}: {err}")));
+        continue;
+      }
+    };
+    match toml::from_str::<toml::Value>(&content) {
+      Ok(layer) => {
+        mark(&layer, ConfigSource::File(path.clone()), &mut sources);
+        merge(&mut merged, layer);
+      }
+      Err(err) => {
+        let line = err.line_col().map(|(line, _)| format!("{path}:{}", line + 1)).unwrap_or(path.clone());
+        problems.push(ConfigProblem::new("syntax", err.to_string()).at(Some(line)));
+      }
+    }
+    files.push((path, content));
+  }
+
+  let mut vars: Vec<(String, String)> = vars.filter(|(name, _)| name.starts_with("MIO_") && name.contains("__")).collect();
+  vars.sort();
+  for (name, raw) in vars {
+    let key = env_key(&name);
+    set_key(&mut merged, &key, parse_value(&raw));
+    sources.insert(key, ConfigSource::Env(name));
+  }
+  for (key, raw) in options.overrides.iter() {
+    set_key(&mut merged, key, parse_value(raw));
+    sources.insert(key.clone(), ConfigSource::Cli);
+  }
+  if !problems.is_empty() { return Err(problems) }
+
+  let origins = Origins { sources, files };
+  let problems = check_structure(&merged, &origins);
+  if !problems.is_empty() { return Err(problems) }
+
+  let config = merged.clone().try_into::<Config>().map_err(|err| vec![ConfigProblem::new("type", err.to_string())])?;
+  let problems = validate(&config, &origins);
+  if !problems.is_empty() { return Err(problems) }
+
+  let mut values = BTreeMap::new();
+  flatten(String::new(), merged, &mut values);
+  Ok(LoadedConfig { config, values, origins })
+}
+
+/// Read the config of `options`, logged once it is loaded
+pub fn read_config(options: &ConfigOptions) -> Result<LoadedConfig, Vec<ConfigProblem>> {
+  let loaded = load_config(options)?;
+  utils::success("ConfigReader", format!("Configuration successfully loaded from {}", options.files().join(" + ")).as_str());
+  Ok(loaded)
+}
+
+/// Tables are flattened as `section.key`, arrays are compared as a whole
+fn flatten(prefix: String, value: toml::Value, values: &mut BTreeMap<String, toml::Value>) {
+  match value {
+    toml::Value::Table(table) => for (key, value) in table {
+      let key = if prefix.is_empty() { key } else { format!("{prefix}.{key}") };
+      flatten(key, value, values);
+    },
+    value => { values.insert(prefix, value); }
+  }
+}
 
-fn parse_config_file(content: &str) -> Result<Config, toml::de::Error> {
-  let parsed: Result<Config, toml::de::Error> = toml::from_str(content);
-  parsed
-}
- 
-pub fn read_config() -> Result<Config, ()> {
-  let config = OpenOptions::new().read(true).write(true).create(true).open(RELATIVE_CONFIG_DIR);
-  match config {
-    Ok(mut cnf_file) => {
-      let mut cnf_body: String = String::new();
-      let _ = cnf_file.read_to_string(&mut cnf_body);
-      match parse_config_file(&cnf_body.as_str()) {
-        Ok(cnf) => {
-          utils::success("ConfigReader", "Configuration successfully loaded");
-          Ok(cnf)
-        },
-        Err(err) => {
-          utils::error("ConfigReader", "cannot parse the configuration", err.to_string().as_str());
-          Err(())
-        }
+/// Record `source` for every key given by a layer
+fn mark(layer: &toml::Value, source: ConfigSource, sources: &mut BTreeMap<String, ConfigSource>) {
+  let mut keys = BTreeMap::new();
+  flatten(String::new(), layer.clone(), &mut keys);
+  for key in keys.into_keys() { sources.insert(key, source.clone()); }
+}
+
+/// Tables are merged key by key, the other values are replaced
+fn merge(base: &mut toml::Value, layer: toml::Value) {
+  match (base, layer) {
+    (toml::Value::Table(base), toml::Value::Table(layer)) => for (key, value) in layer {
+      match base.get_mut(&key) {
+        Some(existing) => merge(existing, value),
+        None => { base.insert(key, value); }
       }
+    },
+    (base, layer) => *base = layer
+  }
+}
+
+fn set_key(root: &mut toml::Value, key: &str, value: toml::Value) {
+  let mut layer = value;
+  for part in key.rsplit('.') {
+    let mut table = toml::value::Table::new();
+    table.insert(part.to_string(), layer);
+    layer = toml::Value::Table(table);
+  }
+  merge(root, layer);
+}
+
+/// `MIO_LATENCY__WARN_MS` → `latency.warn_ms`
+fn env_key(name: &str) -> String {
+  name.strip_prefix("MIO_").unwrap_or(name).to_lowercase().replace("__", ".")
+}
+
+/// A TOML value (`true`, `120`, `["a"]`...), a plain string otherwise
+fn parse_value(raw: &str) -> toml::Value {
+  toml::from_str::<toml::Value>(&format!("value = {raw}"))
+    .ok()
+    .and_then(|table| table.get("value").cloned())
+    .unwrap_or_else(|| toml::Value::String(raw.to_string()))
+}
+
+/// Missing sections and required keys
+fn check_structure(merged: &toml::Value, origins: &Origins) -> Vec<ConfigProblem> {
+  let mut problems = Vec::new();
+  for section in REQUIRED_SECTIONS {
+    match merged.get(section) {
+      None => problems.push(ConfigProblem::new(section, format!("the section [{section}] is missing"))),
+      Some(value) if !value.is_table() => problems.push(ConfigProblem::new(section, "must be a section").at(origins.locate(section))),
+      Some(_) => {}
     }
-    Err(err) => {
-      utils::error("ConfigReader", "cannot read the config file", err.to_string().as_str());
-      Err(())
+  }
+  for (section, key) in REQUIRED_KEYS {
+    if let Some(table) = merged.get(section).and_then(|s| s.as_table()) {
+      if !table.contains_key(key) {
+        problems.push(ConfigProblem::new(&format!("{section}.{key}"), "is required").at(origins.locate(section)));
+      }
     }
   }
+  problems
 }
 
+/// Values which are well typed but cannot be used
+fn validate(config: &Config, origins: &Origins) -> Vec<ConfigProblem> {
+  let mut problems = Vec::new();
+  let params = &config.params;
+  let mut problem = |key: &str, message: String| problems.push(ConfigProblem::new(key, message).at(origins.locate(key)));
+
+  if params.prefix.trim().is_empty() {
+    problem("params.prefix", "cannot be empty".to_string());
+  }
+  if (params.status_time as i64) < status::MIN_STATUS_TIME as i64 {
+    problem("params.status_time", format!("must be at least {} seconds, got {}", status::MIN_STATUS_TIME, params.status_time));
+  }
+  if status::RotationMode::from_str(&params.status_mode).is_none() {
+    problem("params.status_mode", format!("unknown mode `{}`, expected sequential, random or weighted", params.status_mode));
+  }
+  for (i, entry) in params.status.iter().enumerate() {
+    if let Err(err) = status::Status::from_config(entry) { problem(&format!("params.status[{i}]"), err) }
+  }
+  for (key, entry) in [("dev_status", &params.dev_status), ("maintenance_status", &params.maintenance_status), ("debug_status", &params.debug_status)] {
+    if let Err(err) = status::Status::from_config(entry) { problem(&format!("params.{key}"), err) }
+  }
+  if config.latency.interval == 0 {
+    problem("latency.interval", "must be above 0".to_string());
+  }
+  if !["plain", "json"].contains(&config.logging.format.as_str()) {
+    problem("logging.format", format!("unknown format `{}`, expected plain or json", config.logging.format));
+  }
+  let logging = &config.logging;
+  if logging.max_size_mb > logging::MAX_LOG_SIZE_MB {
+    problem("logging.max_size_mb", format!("must be at most {}, got {}", logging::MAX_LOG_SIZE_MB, logging.max_size_mb));
+  }
+  if logging.keep_files > logging::MAX_KEEP_FILES {
+    problem("logging.keep_files", format!("must be at most {}, got {}", logging::MAX_KEEP_FILES, logging.keep_files));
+  }
+  if logging.max_age_days > logging::MAX_AGE_DAYS {
+    problem("logging.max_age_days", format!("must be at most {}, got {}", logging::MAX_AGE_DAYS, logging.max_age_days));
+  }
+  if config.logging.enabled && config.logging.file == config.logging.security_file {
+    problem("logging.security_file", "must be different from logging.file".to_string());
+  }
+  problems
+}
 
-pub const RELATIVE_CONFIG_DIR: &str = "./config.toml";
\ No newline at end of file
+/// Line of `key` in `[section]`, the root of the file for an empty section
+fn line_of(content: &str, section: &str, key: &str) -> Option<usize> {
+  let mut current = "";
+  for (i, line) in content.lines().enumerate() {
+    let line = line.trim();
+    if line == key { return Some(i + 1) }
+    if line.starts_with('[') && !line.starts_with("[[") {
+      current = line.trim_matches(|c| c == '[' || c == ']').trim();
+      continue;
+    }
+    if current == section {
+      if let Some(rest) = line.strip_prefix(key) {
+        if rest.trim_start().starts_with('=') { return Some(i + 1) }
+      }
+    }
+  }
+  None
+}
+
+/// Line of the `index`th entry of an array of inline tables, written one per line
+fn line_of_entry(content: &str, section: &str, key: &str, index: usize) -> Option<usize> {
+  let start = line_of(content, section, key)?;
+  content.lines().enumerate().skip(start)
+    .filter(|(_, line)| line.trim_start().starts_with('{'))
+    .nth(index)
+    .map(|(i, _)| i + 1)
+}
+
+
+pub const RELATIVE_CONFIG_DIR: &str = "./config.toml";
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const BASE: &str = r#"
+    [client]
+    version = "0.0.1"
+    build_type = "alpha"
+
+    [params]
+    prefix = "&"
+
+    [security]
+
+    [i18n]
+    locales_dir = "./src/assets/languages"
+  "#;
+
+  /// [BASE] with `section.key` values, parsed like the `--set` flags
+  fn config(values: &[(&str, &str)]) -> Config {
+    let mut merged: toml::Value = toml::from_str(BASE).unwrap();
+    for (key, raw) in values { set_key(&mut merged, key, parse_value(raw)) }
+    merged.try_into().unwrap()
+  }
+
+  fn no_origins() -> Origins {
+    Origins { sources: BTreeMap::new(), files: Vec::new() }
+  }
+
+  fn problems(values: &[(&str, &str)]) -> Vec<String> {
+    validate(&config(values), &no_origins()).into_iter().map(|p| p.key).collect()
+  }
+
+  #[test]
+  fn defaults_are_valid() {
+    assert!(problems(&[]).is_empty());
+  }
+
+  #[test]
+  fn status_time_has_a_minimum() {
+    let min = status::MIN_STATUS_TIME.to_string();
+    assert!(problems(&[("params.status_time", &min)]).is_empty());
+    assert_eq!(problems(&[("params.status_time", "0")]), ["params.status_time"]);
+    assert_eq!(problems(&[("params.status_time", "-5")]), ["params.status_time"]);
+  }
+
+  #[test]
+  fn logging_limits() {
+    assert!(problems(&[("logging.max_size_mb", "0"), ("logging.keep_files", "0"), ("logging.max_age_days", "0")]).is_empty());
+    let found = problems(&[
+      ("logging.max_size_mb", "100000000000000"),
+      ("logging.keep_files", "1000000"),
+      ("logging.max_age_days", "100000")
+    ]);
+    assert_eq!(found, ["logging.max_size_mb", "logging.keep_files", "logging.max_age_days"]);
+  }
+
+  #[test]
+  fn every_problem_is_reported() {
+    let found = problems(&[
+      ("params.prefix", "\"  \""),
+      ("params.status_mode", "shuffle"),
+      ("latency.interval", "0"),
+      ("logging.format", "xml")
+    ]);
+    assert_eq!(found, ["params.prefix", "params.status_mode", "latency.interval", "logging.format"]);
+  }
+
+  #[test]
+  fn layers_are_applied_in_order() {
+    let dir = env::temp_dir().join(format!("mio-config-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("config.toml");
+    fs::write(&path, format!("{BASE}\n[latency]\nwarn_ms = 400\nrecover_ms = 200\nconsecutive = 2\nwebhook_url = \"https://example.com/hook\"\n")).unwrap();
+    fs::write(dir.join("config.dev.toml"), "[latency]\nrecover_ms = 250\nconsecutive = 4\n").unwrap();
+    let vars = [
+      ("MIO_LATENCY__CONSECUTIVE", "6"),
+      ("MIO_LATENCY__INTERVAL", "8"),
+      ("MIO_LATENCY__ALERT_FILE", "./alerts.log"),
+      ("MIO_IGNORED", "1")
+    ].map(|(name, value)| (name.to_string(), value.to_string()));
+
+    let options = ConfigOptions {
+      path: Some(path.to_string_lossy().to_string()),
+      env: Some("dev".to_string()),
+      overrides: vec![("latency.interval".to_string(), "7".to_string())]
+    };
+    let loaded = load_config_with(&options, vars.into_iter());
+    fs::remove_dir_all(&dir).ok();
+    let loaded = loaded.unwrap_or_else(|problems| panic!("{problems:?}"));
+
+    let latency = &loaded.config.latency;
+    assert_eq!((latency.warn_ms, latency.recover_ms, latency.consecutive, latency.interval), (400, 250, 6, 7));
+    assert_eq!(latency.alert_file, "./alerts.log");
+    assert_eq!(latency.history, default_latency_history());
+
+    let overlay = dir.join("config.dev.toml").to_string_lossy().to_string();
+    assert_eq!(loaded.source_of("latency.warn_ms"), ConfigSource::File(path.to_string_lossy().to_string()));
+    assert_eq!(loaded.source_of("latency.recover_ms"), ConfigSource::File(overlay));
+    assert_eq!(loaded.source_of("latency.consecutive"), ConfigSource::Env("MIO_LATENCY__CONSECUTIVE".to_string()));
+    assert_eq!(loaded.source_of("latency.interval"), ConfigSource::Cli);
+    assert_eq!(loaded.source_of("latency.history"), ConfigSource::Default);
+
+    let described = loaded.describe();
+    let shown = |key: &str| described.iter().find(|(k, _, _)| k == key).map(|(_, value, _)| value.clone()).unwrap();
+    assert_eq!(shown("latency.webhook_url"), "<redacted>");
+    assert_eq!(shown("latency.warn_ms"), "400");
+  }
+
+  #[test]
+  fn env_variables_are_toml_values() {
+    assert_eq!(env_key("MIO_PARAMS__PREFIX"), "params.prefix");
+    assert_eq!(env_key("MIO_LATENCY__WARN_MS"), "latency.warn_ms");
+
+    assert_eq!(parse_value("true"), toml::Value::Boolean(true));
+    assert_eq!(parse_value("120"), toml::Value::Integer(120));
+    assert_eq!(parse_value("[1, 2]"), toml::Value::Array(vec![toml::Value::Integer(1), toml::Value::Integer(2)]));
+    assert_eq!(parse_value("\"quoted\""), toml::Value::String("quoted".to_string()));
+    assert_eq!(parse_value("!"), toml::Value::String("!".to_string()));
+    assert_eq!(parse_value("la neige ☃️"), toml::Value::String("la neige ☃️".to_string()));
+  }
+
+  #[test]
+  fn secrets_are_recognized() {
+    for key in ["client.token", "database.password", "latency.webhook_url", "security.api_key", "oauth.client_secret"] {
+      assert!(is_secret(key), "{key}");
+    }
+    for key in ["params.prefix", "security.auto_save_archive", "logging.security_file", "logging.keep_files"] {
+      assert!(!is_secret(key), "{key}");
+    }
+  }
+
+  #[test]
+  fn invalid_statuses_are_located() {
+    assert_eq!(problems(&[("params.status", r#"[{ status_type = "WATCHING", message = "ok" }, { status_type = "DANCING", message = "no" }]"#)]), ["params.status[1]"]);
+  }
+}
\ No newline at end of file
@@ -0,0 +1,552 @@
+use std::{ collections::{ HashMap, VecDeque }, fs::OpenOptions, io::Write, sync::Arc, time::{ Duration, Instant } };
+use chrono::Local;
+use serenity::{
+  client::bridge::gateway::{ ShardId, ShardManager, ShardMessenger },
+  gateway::ConnectionStage,
+  http::Http,
+  model::user::OnlineStatus,
+  prelude::{ Mutex, RwLock, TypeMap }
+};
+use tokio::time::sleep;
+
+use crate::{ utils, init::{ LatencyConfig, WatchdogConfig }, storage::{ Latency, Storage } };
+
+/// Last latencies of a shard, the oldest sample is dropped once `capacity` is reached
+#[derive(Debug, Clone, Default)]
+pub struct LatencyHistory {
+  samples: VecDeque<Duration>,
+  capacity: usize
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct LatencyStats {
+  pub min: Duration,
+  pub avg: Duration,
+  pub p95: Duration,
+  pub max: Duration
+}
+
+impl LatencyStats {
+  pub fn describe(&self) -> String {
+    format!(
+      "min {}ms, avg {}ms, p95 {}ms, max {}ms",
+      self.min.as_millis(), self.avg.as_millis(), self.p95.as_millis(), self.max.as_millis()
+    )
+  }
+}
+
+impl LatencyHistory {
+  pub fn new(capacity: usize) -> LatencyHistory {
+    LatencyHistory { samples: VecDeque::with_capacity(capacity), capacity: capacity.max(1) }
+  }
+
+  pub fn push(&mut self, ping: Duration) {
+    if self.samples.len() >= self.capacity { self.samples.pop_front(); }
+    self.samples.push_back(ping);
+  }
+
+  pub fn len(&self) -> usize {
+    self.samples.len()
+  }
+
+  /// The `count` most recent samples, newest first
+  pub fn recent(&self, count: usize) -> impl Iterator<Item = &Duration> {
+    self.samples.iter().rev().take(count)
+  }
+
+  /// Nearest-rank percentile, `percent` between 0 and 100
+  pub fn percentile(&self, percent: f64) -> Option<Duration> {
+    if self.samples.is_empty() { return None }
+    let mut sorted: Vec<Duration> = self.samples.iter().copied().collect();
+    sorted.sort();
+    let rank = ((percent / 100.0) * sorted.len() as f64).ceil() as usize;
+    Some(sorted[rank.clamp(1, sorted.len()) - 1])
+  }
+
+  pub fn stats(&self) -> Option<LatencyStats> {
+    if self.samples.is_empty() { return None }
+    let total: Duration = self.samples.iter().sum();
+    Some(LatencyStats {
+      min: *self.samples.iter().min()?,
+      avg: total / self.samples.len() as u32,
+      p95: self.percentile(95.0)?,
+      max: *self.samples.iter().max()?
+    })
+  }
+}
+
+// ==================================
+// alerts
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ShardHealth {
+  Normal,
+  Degraded
+}
+
+pub struct Alert {
+  pub shard: u64,
+  pub health: ShardHealth,
+  pub ping: Duration,
+  pub stats: Option<LatencyStats>
+}
+
+impl Alert {
+  pub fn message(&self) -> String {
+    let stats = self.stats.map(|s| format!(" ({})", s.describe())).unwrap_or_default();
+    match self.health {
+      ShardHealth::Degraded => format!(
+        "The shard {} have a latency of {}ms, the ping is to high and may cause user-side latency{stats}",
+        self.shard, self.ping.as_millis()
+      ),
+      ShardHealth::Normal => format!("The shard {} have a latency of {}ms, the ping is now normal{stats}", self.shard, self.ping.as_millis())
+    }
+  }
+}
+
+/// Where the degradations and recoveries of the shards are reported
+pub enum AlertSink {
+  Log,
+  /// url of a Discord webhook
+  Webhook(String),
+  /// file the alerts are appended to
+  File(String)
+}
+
+impl AlertSink {
+  pub fn from_config(config: &LatencyConfig) -> Vec<AlertSink> {
+    config.alert_sinks.iter()
+      .filter_map(|sink| match sink.to_lowercase().as_str() {
+        "log" => Some(AlertSink::Log),
+        "webhook" => match &config.webhook_url {
+          Some(url) if !url.is_empty() => Some(AlertSink::Webhook(url.clone())),
+          _ => {
+            utils::warn("ShardLatency", "The webhook alert sink needs `webhook_url`, ignored");
+            None
+          }
+        },
+        "file" => Some(AlertSink::File(config.alert_file.clone())),
+        other => {
+          utils::warn("ShardLatency", format!("Unknown alert sink `{other}`, ignored").as_str());
+          None
+        }
+      })
+      .collect()
+  }
+
+  pub async fn send(&self, http: &Http, alert: &Alert) {
+    let result = match self {
+      AlertSink::Log => {
+        match alert.health {
+          ShardHealth::Degraded => utils::warn("ShardLatency", alert.message().as_str()),
+          ShardHealth::Normal => utils::info("ShardLatency", alert.message().as_str())
+        }
+        Ok(())
+      }
+      AlertSink::Webhook(url) => {
+        let icon = if alert.health == ShardHealth::Degraded { "🔴" } else { "🟢" };
+        match http.get_webhook_from_url(url).await {
+          Ok(webhook) => webhook.execute(http, false, |w| w.content(format!("{icon} {}", alert.message()))).await
+            .map(|_| ())
+            .map_err(|e| e.to_string()),
+          Err(err) => Err(err.to_string())
+        }
+      }
+      AlertSink::File(path) => {
+        OpenOptions::new().create(true).append(true).open(path)
+          .and_then(|mut file| writeln!(file, "({}) {}", utils::format_date(Local::now(), "%d/%m/%Y %H:%M:%S"), alert.message()))
+          .map_err(|e| e.to_string())
+      }
+    };
+    if let Err(err) = result {
+      utils::error("ShardLatency", "cannot send the latency alert", err.as_str());
+    }
+  }
+}
+
+// ==================================
+// watchdog
+
+/// Why a shard is considered stuck
+#[derive(Debug, Clone, PartialEq)]
+pub enum StuckReason {
+  NoHeartbeatAck(Duration),
+  StaleLatency(Duration),
+  Disconnected(Duration),
+  RepeatedDisconnects(usize)
+}
+
+impl StuckReason {
+  pub fn as_str(&self) -> String {
+    match self {
+      StuckReason::NoHeartbeatAck(since) => format!("no heartbeat acknowledged for {}s", since.as_secs()),
+      StuckReason::StaleLatency(since) => format!("the latency did not change for {}s", since.as_secs()),
+      StuckReason::Disconnected(since) => format!("not connected for {}s", since.as_secs()),
+      StuckReason::RepeatedDisconnects(count) => format!("{count} disconnections in a short time")
+    }
+  }
+}
+
+/// Limits after which the watchdog restarts a shard
+pub struct WatchdogRules {
+  pub no_ack_after: Duration,
+  pub stale_after: Duration,
+  pub disconnected_after: Duration,
+  pub max_disconnects: usize,
+  pub disconnect_window: Duration,
+  pub restart_cooldown: Duration
+}
+
+impl WatchdogRules {
+  pub fn from_config(config: &WatchdogConfig) -> WatchdogRules {
+    WatchdogRules {
+      no_ack_after: Duration::from_secs(config.no_ack_after),
+      stale_after: Duration::from_secs(config.stale_after),
+      disconnected_after: Duration::from_secs(config.disconnected_after),
+      max_disconnects: config.max_disconnects.max(1),
+      disconnect_window: Duration::from_secs(config.disconnect_window),
+      restart_cooldown: Duration::from_secs(config.restart_cooldown)
+    }
+  }
+}
+
+/// What the watchdog remembers of a shard between two samples
+#[derive(Debug, Clone, Default)]
+pub struct ShardWatch {
+  connected: bool,
+  disconnected_since: Option<Instant>,
+  no_ack_since: Option<Instant>,
+  /// last latency reported and since when it did not change
+  stale_since: Option<(Duration, Instant)>,
+  disconnects: VecDeque<Instant>,
+  last_restart: Option<Instant>,
+  /// disconnections since the start of the process
+  pub total_disconnects: u32
+}
+
+impl ShardWatch {
+  /// Record a sample of the shard, returns why it should be restarted if it is stuck
+  pub fn observe(&mut self, now: Instant, connected: bool, latency: Option<Duration>, rules: &WatchdogRules) -> Option<StuckReason> {
+    if self.connected && !connected {
+      self.total_disconnects += 1;
+      self.disconnects.push_back(now);
+    }
+    self.connected = connected;
+    while self.disconnects.front().map(|at| now.duration_since(*at) > rules.disconnect_window).unwrap_or(false) {
+      self.disconnects.pop_front();
+    }
+
+    self.disconnected_since = if connected { None } else { Some(self.disconnected_since.unwrap_or(now)) };
+    self.no_ack_since = match latency {
+      Some(_) => None,
+      None => Some(self.no_ack_since.unwrap_or(now))
+    };
+    self.stale_since = match (latency, self.stale_since) {
+      (Some(ping), Some((last, since))) if ping == last => Some((last, since)),
+      (Some(ping), _) => Some((ping, now)),
+      (None, _) => None
+    };
+
+    // a restarted shard gets some time to come back
+    if self.last_restart.map(|at| now.duration_since(at) < rules.restart_cooldown).unwrap_or(false) {
+      return None
+    }
+
+    let elapsed = |since: Option<Instant>| since.map(|at| now.duration_since(at)).unwrap_or_default();
+    if self.disconnects.len() >= rules.max_disconnects {
+      Some(StuckReason::RepeatedDisconnects(self.disconnects.len()))
+    } else if elapsed(self.disconnected_since) > rules.disconnected_after {
+      Some(StuckReason::Disconnected(elapsed(self.disconnected_since)))
+    } else if elapsed(self.no_ack_since) > rules.no_ack_after {
+      Some(StuckReason::NoHeartbeatAck(elapsed(self.no_ack_since)))
+    } else if elapsed(self.stale_since.map(|(_, since)| since)) > rules.stale_after {
+      Some(StuckReason::StaleLatency(elapsed(self.stale_since.map(|(_, since)| since))))
+    } else {
+      None
+    }
+  }
+
+  /// Forget the incidents once the shard is restarted
+  pub fn restarted(&mut self, now: Instant) {
+    *self = ShardWatch { last_restart: Some(now), total_disconnects: self.total_disconnects, ..Default::default() };
+  }
+}
+
+// ==================================
+// monitor
+
+/// Warn and recover thresholds, the gap between them avoids flapping around a single value
+pub struct Thresholds {
+  pub warn: Duration,
+  pub recover: Duration,
+  /// samples in a row above `warn` before the shard is degraded
+  pub consecutive: usize
+}
+
+impl Thresholds {
+  pub fn from_config(config: &LatencyConfig) -> Thresholds {
+    Thresholds {
+      warn: Duration::from_millis(config.warn_ms),
+      recover: Duration::from_millis(config.recover_ms.min(config.warn_ms)),
+      consecutive: config.consecutive.max(1)
+    }
+  }
+
+  /// New health of a shard when its last samples cross a threshold
+  pub fn evaluate(&self, latency: &Latency) -> Option<ShardHealth> {
+    let degraded = latency.history.len() >= self.consecutive
+      && latency.history.recent(self.consecutive).all(|ping| *ping > self.warn);
+    if !latency.warned && degraded { Some(ShardHealth::Degraded) }
+    else if latency.warned && latency.ping < self.recover { Some(ShardHealth::Normal) }
+    else { None }
+  }
+}
+
+/// Sample the latency of every shard, keep its history in [Storage::latency], alert when a
+/// shard degrades or recovers and restart the shards the watchdog finds stuck
+pub async fn monitor(
+  data: Arc<RwLock<TypeMap>>,
+  shard_manager: Arc<Mutex<ShardManager>>,
+  http: Arc<Http>,
+  config: LatencyConfig,
+  watchdog: WatchdogConfig
+) {
+  let storage_lock = {
+    let data = data.read().await;
+    data.get::<Storage>().expect("Expected Storage in TypeMap").clone()
+  };
+  let thresholds = Thresholds::from_config(&config);
+  let rules = WatchdogRules::from_config(&watchdog);
+  let sinks = AlertSink::from_config(&config);
+  let mut shards: HashMap<u64, Latency> = HashMap::new();
+  let mut watches: HashMap<u64, ShardWatch> = HashMap::new();
+
+  loop {
+    sleep(Duration::from_secs(config.interval.max(1))).await;
+
+    let runners: Vec<(u64, ShardMessenger, Option<Duration>, ConnectionStage)> = {
+      let manager = shard_manager.lock().await;
+      let runners = manager.runners.lock().await;
+      runners.iter().map(|(id, runner)| (id.0, runner.runner_tx.clone(), runner.latency, runner.stage)).collect()
+    };
+
+    let now = Instant::now();
+    let mut alerts: Vec<Alert> = Vec::new();
+    let mut stuck: Vec<(u64, StuckReason)> = Vec::new();
+    for (id, messenger, ping, stage) in runners.iter() {
+      let latency = shards.entry(*id).or_insert_with(|| Latency {
+        ping: Duration::ZERO,
+        warned: false,
+        history: LatencyHistory::new(config.history),
+        restarts: 0,
+        disconnects: 0
+      });
+
+      if watchdog.enabled {
+        let watch = watches.entry(*id).or_default();
+        if let Some(reason) = watch.observe(now, *stage == ConnectionStage::Connected, *ping, &rules) {
+          stuck.push((*id, reason));
+        }
+        latency.disconnects = watch.total_disconnects;
+      }
+
+      // no heartbeat acknowledged yet, nothing to record
+      let ping = match ping {
+        Some(ping) => *ping,
+        None => continue
+      };
+      latency.ping = ping;
+      latency.history.push(ping);
+
+      if let Some(health) = thresholds.evaluate(latency) {
+        latency.warned = health == ShardHealth::Degraded;
+        messenger.set_status(if latency.warned { OnlineStatus::DoNotDisturb } else { OnlineStatus::Online });
+        alerts.push(Alert { shard: *id, health, ping, stats: latency.history.stats() });
+      }
+    }
+    shards.retain(|id, _| runners.iter().any(|(shard, _, _, _)| shard == id));
+
+    for (id, reason) in stuck.iter() {
+      utils::warn_with_cause("ShardWatchdog", format!("The shard {id} looks stuck, restarting it").as_str(), reason.as_str().as_str());
+      shard_manager.lock().await.restart(ShardId(*id)).await;
+      if let Some(watch) = watches.get_mut(id) { watch.restarted(Instant::now()) }
+      if let Some(latency) = shards.get_mut(id) { latency.restarts += 1 }
+    }
+
+    storage_lock.write().await.latency = shards.clone();
+
+    for alert in alerts.iter() {
+      for sink in sinks.iter() {
+        sink.send(&http, alert).await;
+      }
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn ms(value: u64) -> Duration {
+    Duration::from_millis(value)
+  }
+
+  fn history(samples: &[u64]) -> LatencyHistory {
+    let mut history = LatencyHistory::new(samples.len());
+    for sample in samples { history.push(ms(*sample)) }
+    history
+  }
+
+  fn latency(samples: &[u64], warned: bool) -> Latency {
+    let ping = ms(*samples.last().unwrap_or(&0));
+    Latency { ping, warned, history: history(samples), restarts: 0, disconnects: 0 }
+  }
+
+  #[test]
+  fn history_drops_the_oldest_samples() {
+    let mut history = LatencyHistory::new(3);
+    for sample in [10, 20, 30, 40] { history.push(ms(sample)) }
+    assert_eq!(history.len(), 3);
+    assert_eq!(history.recent(2).copied().collect::<Vec<_>>(), vec![ms(40), ms(30)]);
+    assert_eq!(history.recent(10).count(), 3);
+  }
+
+  #[test]
+  fn percentile_uses_the_nearest_rank() {
+    assert_eq!(LatencyHistory::new(5).percentile(95.0), None);
+
+    let five = history(&[50, 10, 40, 20, 30]);
+    assert_eq!(five.percentile(0.0), Some(ms(10)));
+    assert_eq!(five.percentile(20.0), Some(ms(10)));
+    assert_eq!(five.percentile(50.0), Some(ms(30)));
+    assert_eq!(five.percentile(95.0), Some(ms(50)));
+    assert_eq!(five.percentile(100.0), Some(ms(50)));
+
+    let samples: Vec<u64> = (1..=100).rev().collect();
+    assert_eq!(history(&samples).percentile(95.0), Some(ms(95)));
+    let samples: Vec<u64> = (1..=20).collect();
+    assert_eq!(history(&samples).percentile(95.0), Some(ms(19)));
+  }
+
+  #[test]
+  fn stats_of_the_history() {
+    assert!(LatencyHistory::new(5).stats().is_none());
+    let stats = history(&[40, 10, 100, 50]).stats().unwrap();
+    assert_eq!((stats.min, stats.avg, stats.p95, stats.max), (ms(10), ms(50), ms(100), ms(100)));
+  }
+
+  #[test]
+  fn degraded_after_consecutive_high_samples() {
+    let thresholds = Thresholds { warn: ms(200), recover: ms(150), consecutive: 3 };
+    assert_eq!(thresholds.evaluate(&latency(&[250, 250], false)), None);
+    assert_eq!(thresholds.evaluate(&latency(&[250, 250, 250], false)), Some(ShardHealth::Degraded));
+    assert_eq!(thresholds.evaluate(&latency(&[250, 100, 250, 250], false)), None);
+    assert_eq!(thresholds.evaluate(&latency(&[100, 250, 250, 250], false)), Some(ShardHealth::Degraded));
+    // exactly on the threshold is not above it
+    assert_eq!(thresholds.evaluate(&latency(&[200, 200, 200], false)), None);
+  }
+
+  #[test]
+  fn recovery_needs_the_lower_threshold() {
+    let thresholds = Thresholds { warn: ms(200), recover: ms(150), consecutive: 3 };
+    // already degraded, no new alert
+    assert_eq!(thresholds.evaluate(&latency(&[250, 250, 250], true)), None);
+    // between the two thresholds the health does not change either way
+    assert_eq!(thresholds.evaluate(&latency(&[250, 250, 180], true)), None);
+    assert_eq!(thresholds.evaluate(&latency(&[180, 180, 180], false)), None);
+    assert_eq!(thresholds.evaluate(&latency(&[250, 250, 150], true)), None);
+    assert_eq!(thresholds.evaluate(&latency(&[250, 250, 140], true)), Some(ShardHealth::Normal));
+  }
+
+  fn rules() -> WatchdogRules {
+    WatchdogRules {
+      no_ack_after: Duration::from_secs(30),
+      stale_after: Duration::from_secs(60),
+      disconnected_after: Duration::from_secs(20),
+      max_disconnects: 3,
+      disconnect_window: Duration::from_secs(120),
+      restart_cooldown: Duration::from_secs(30)
+    }
+  }
+
+  fn at(start: Instant, secs: u64) -> Instant {
+    start + Duration::from_secs(secs)
+  }
+
+  #[test]
+  fn healthy_shard_is_left_alone() {
+    let (mut watch, start, rules) = (ShardWatch::default(), Instant::now(), rules());
+    for i in 0..100 {
+      assert_eq!(watch.observe(at(start, i * 10), true, Some(ms(40 + i % 2)), &rules), None);
+    }
+  }
+
+  #[test]
+  fn missing_heartbeat_ack() {
+    let (mut watch, start, rules) = (ShardWatch::default(), Instant::now(), rules());
+    assert_eq!(watch.observe(start, true, None, &rules), None);
+    assert_eq!(watch.observe(at(start, 30), true, None, &rules), None);
+    assert_eq!(watch.observe(at(start, 31), true, None, &rules), Some(StuckReason::NoHeartbeatAck(Duration::from_secs(31))));
+    // an acknowledged heartbeat resets it
+    assert_eq!(watch.observe(at(start, 32), true, Some(ms(40)), &rules), None);
+  }
+
+  #[test]
+  fn latency_that_never_changes() {
+    let (mut watch, start, rules) = (ShardWatch::default(), Instant::now(), rules());
+    assert_eq!(watch.observe(start, true, Some(ms(42)), &rules), None);
+    assert_eq!(watch.observe(at(start, 60), true, Some(ms(42)), &rules), None);
+    assert_eq!(watch.observe(at(start, 61), true, Some(ms(42)), &rules), Some(StuckReason::StaleLatency(Duration::from_secs(61))));
+    assert_eq!(watch.observe(at(start, 62), true, Some(ms(43)), &rules), None);
+  }
+
+  #[test]
+  fn disconnected_for_too_long() {
+    let (mut watch, start, rules) = (ShardWatch::default(), Instant::now(), rules());
+    assert_eq!(watch.observe(start, true, Some(ms(40)), &rules), None);
+    assert_eq!(watch.observe(at(start, 1), false, None, &rules), None);
+    assert_eq!(watch.observe(at(start, 21), false, None, &rules), None);
+    assert_eq!(watch.observe(at(start, 22), false, None, &rules), Some(StuckReason::Disconnected(Duration::from_secs(21))));
+    assert_eq!(watch.total_disconnects, 1);
+  }
+
+  #[test]
+  fn repeated_disconnections_in_the_window() {
+    let (mut watch, start, rules) = (ShardWatch::default(), Instant::now(), rules());
+    let mut result = None;
+    for i in 0..6 {
+      let connected = i % 2 == 0;
+      result = watch.observe(at(start, i), connected, connected.then(|| ms(40 + i)), &rules);
+      if i < 5 { assert_eq!(result, None, "sample {i}") }
+    }
+    assert_eq!(result, Some(StuckReason::RepeatedDisconnects(3)));
+
+    // the same disconnections spread over more than the window are forgotten
+    let mut watch = ShardWatch::default();
+    for i in 0..6 {
+      let connected = i % 2 == 0;
+      assert_eq!(watch.observe(at(start, i * 100), connected, Some(ms(40 + i)), &rules), None, "sample {i}");
+      if connected { continue }
+      // reconnect right away so the shard is not disconnected for too long
+      assert_eq!(watch.observe(at(start, i * 100 + 1), true, Some(ms(50 + i)), &rules), None);
+    }
+    assert_eq!(watch.total_disconnects, 3);
+  }
+
+  #[test]
+  fn restarted_shard_gets_a_cooldown() {
+    let (mut watch, start, rules) = (ShardWatch::default(), Instant::now(), rules());
+    assert_eq!(watch.observe(start, true, Some(ms(40)), &rules), None);
+    assert_eq!(watch.observe(at(start, 1), false, None, &rules), None);
+    assert!(watch.observe(at(start, 30), false, None, &rules).is_some());
+
+    watch.restarted(at(start, 30));
+    assert_eq!(watch.total_disconnects, 1);
+    // still down, but the restart is given some time
+    assert_eq!(watch.observe(at(start, 31), false, None, &rules), None);
+    assert_eq!(watch.observe(at(start, 59), false, None, &rules), None);
+    // the incidents before the restart are forgotten, the new ones are counted from the next sample
+    assert_eq!(watch.observe(at(start, 60), false, None, &rules), Some(StuckReason::Disconnected(Duration::from_secs(29))));
+  }
+}
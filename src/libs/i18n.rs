@@ -18,7 +18,7 @@
 //}
 
 use crate::utils;
-use std::{ collections::HashMap, path::Path, fs, process::exit };
+use std::collections::{ BTreeMap, HashMap };
 use once_cell::sync::Lazy;
 use serde_json::Value;
 use serenity::prelude::RwLock;
@@ -46,7 +46,7 @@ impl Languages {
   }
 
   fn add_lang(&mut self, name: &String, content: &Value, force: bool) -> Result<(), LanguageClientError> {
-    if let Some(lang) = self.langs.get_mut(&*name) {
+    if let Some(lang) = self.langs.get_mut(name) {
       if force {
         *lang = content.clone();
         Ok(())
@@ -120,10 +120,102 @@ static LANGUAGES: Lazy<RwLock<Languages>> = Lazy::new(|| {
 //      format!($($args),*)
 //  }};
 //}
-//
-//fn translate(path: &str, args: HashMap<&str, &str>){
-//
-//}
+
+/// Language used when the language of the user is unknown
+pub const DEFAULT_LANG: &str = "fr";
+
+impl Languages {
+  fn lookup(&self, lang: &str, path: &str) -> Option<String> {
+    let root = self.langs.get(lang)?;
+    let mut value = root;
+    for key in path.split('.') {
+      value = value.get(key)?;
+    }
+    value.as_str().map(|s| s.to_string())
+  }
+}
+
+/// Translate `path` (keys separated by dots) in `lang` and replace every `{name}` with its value in `args`.
+///
+/// Falls back on the embedded file of the language, then on [DEFAULT_LANG], then returns `path`.
+pub async fn translate(lang: &str, path: &str, args: &[(&str, String)]) -> String {
+  match try_translate(lang, path, args).await {
+    Some(text) => text,
+    None => {
+      utils::warn("LanguageHandler", format!("Missing translation for `{path}`").as_str());
+      path.to_string()
+    }
+  }
+}
+
+/// Same as [translate], `None` when no language defines `path`
+pub async fn try_translate(lang: &str, path: &str, args: &[(&str, String)]) -> Option<String> {
+  let langs = LANGUAGES.read().await;
+  let candidates = [
+    lang.to_string(),
+    format!("{lang}_default"),
+    DEFAULT_LANG.to_string(),
+    format!("{DEFAULT_LANG}_default")
+  ];
+
+  let mut text = candidates.iter().find_map(|l| langs.lookup(l, path))?;
+  for (name, value) in args {
+    text = text.replace(&format!("{{{name}}}"), value);
+  }
+  Some(text)
+}
+
+/// Differences between every language and [DEFAULT_LANG]: missing or unknown keys and placeholders
+pub async fn check() -> Vec<String> {
+  let langs = LANGUAGES.read().await;
+  let reference_name = if langs.langs.contains_key(DEFAULT_LANG) { DEFAULT_LANG.to_string() } else { format!("{DEFAULT_LANG}_default") };
+  let reference = texts(&langs.langs[&reference_name]);
+
+  let mut names: Vec<&String> = langs.langs.keys().filter(|name| **name != reference_name).collect();
+  names.sort();
+
+  let mut problems = Vec::new();
+  for name in names {
+    let lang = texts(&langs.langs[name]);
+    for (path, text) in reference.iter() {
+      match lang.get(path) {
+        None => problems.push(format!("{name}: `{path}` is missing")),
+        Some(translated) if placeholders(text) != placeholders(translated) => {
+          problems.push(format!("{name}: `{path}` does not use the placeholders of {reference_name}"))
+        }
+        Some(_) => {}
+      }
+    }
+    for path in lang.keys().filter(|path| !reference.contains_key(*path)) {
+      problems.push(format!("{name}: `{path}` is unknown in {reference_name}"));
+    }
+  }
+  problems
+}
+
+/// Every text of a language file by its path
+fn texts(root: &Value) -> BTreeMap<String, String> {
+  fn walk(prefix: String, value: &Value, texts: &mut BTreeMap<String, String>) {
+    match value {
+      Value::Object(map) => for (key, value) in map {
+        let path = if prefix.is_empty() { key.clone() } else { format!("{prefix}.{key}") };
+        walk(path, value, texts);
+      },
+      Value::String(text) => { texts.insert(prefix, text.clone()); },
+      _ => {}
+    }
+  }
+  let mut texts = BTreeMap::new();
+  walk(String::new(), root, &mut texts);
+  texts
+}
+
+fn placeholders(text: &str) -> Vec<&str> {
+  let mut names: Vec<&str> = text.split('{').skip(1).filter_map(|part| part.split_once('}').map(|(name, _)| name)).collect();
+  names.sort();
+  names.dedup();
+  names
+}
 
 pub async fn load(_: &String){}
 
@@ -0,0 +1,5 @@
+pub mod i18n;
+#[cfg(feature = "private")]
+pub mod security;
+#[cfg(feature = "private")]
+pub mod database;
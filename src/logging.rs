@@ -0,0 +1,469 @@
+use std::{
+  env, fs, thread,
+  fs::{ File, OpenOptions },
+  io::{ self, IsTerminal, Write },
+  path::{ Path, PathBuf },
+  sync::{ Mutex, RwLock },
+  time::{ Duration, SystemTime }
+};
+use chrono::{ DateTime, Local, NaiveDate, SecondsFormat };
+use flate2::{ write::GzEncoder, Compression };
+use once_cell::sync::Lazy;
+use serde_json::json;
+
+use crate::{ init::LoggingConfig, utils::format_date };
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+  Error,
+  Warn,
+  Info,
+  Debug
+}
+
+impl Level {
+  pub fn from_str(value: &str) -> Option<Level> {
+    match value.trim().to_lowercase().as_str() {
+      "error" => Some(Level::Error),
+      "warn" | "warning" => Some(Level::Warn),
+      "info" => Some(Level::Info),
+      "debug" => Some(Level::Debug),
+      _ => None
+    }
+  }
+
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      Level::Error => "error",
+      Level::Warn => "warn",
+      Level::Info => "info",
+      Level::Debug => "debug"
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, clap::ValueEnum)]
+pub enum Format {
+  /// colored text, plain text when the output is not a terminal
+  Auto,
+  Pretty,
+  Plain,
+  /// one JSON object per line, for log aggregation
+  Json
+}
+
+/// Default level and levels per module (the type given to the utils helpers), `info,StatusLoop=debug`
+#[derive(Debug, Clone)]
+pub struct Filter {
+  default: Level,
+  modules: Vec<(String, Level)>
+}
+
+impl Default for Filter {
+  fn default() -> Self {
+    Filter { default: Level::Info, modules: Vec::new() }
+  }
+}
+
+impl Filter {
+  pub fn parse(spec: &str) -> Result<Filter, String> {
+    let mut filter = Filter::default();
+    for part in spec.split(',').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+      match part.split_once('=') {
+        Some((module, level)) => {
+          let level = Level::from_str(level).ok_or(format!("unknown level `{level}` for `{module}`"))?;
+          filter.modules.push((module.trim().to_lowercase(), level));
+        }
+        None => filter.default = Level::from_str(part).ok_or(format!("unknown level `{part}`"))?
+      }
+    }
+    Ok(filter)
+  }
+
+  pub fn enabled(&self, level: Level, module: &str) -> bool {
+    let module = module.to_lowercase();
+    let max = self.modules.iter().find(|(m, _)| *m == module).map(|(_, l)| *l).unwrap_or(self.default);
+    level <= max
+  }
+}
+
+struct Logger {
+  filter: Filter,
+  format: Format
+}
+
+static LOGGER: Lazy<RwLock<Logger>> = Lazy::new(|| {
+  // the env is read so the messages logged before init are filtered too
+  let filter = env::var("MIO_LOG").ok().and_then(|spec| Filter::parse(&spec).ok()).unwrap_or_default();
+  RwLock::new(Logger { filter, format: Format::Auto })
+});
+
+/// Set the filter and the format, `MIO_LOG` is used when `spec` is `None`.
+/// An invalid filter is replaced by `info`, the format is always applied
+pub fn init(spec: Option<&str>, format: Format) -> Result<(), String> {
+  let spec = spec.map(|s| s.to_string()).or_else(|| env::var("MIO_LOG").ok()).unwrap_or_else(|| "info".to_string());
+  let filter = Filter::parse(&spec);
+  let mut logger = LOGGER.write().unwrap_or_else(|e| e.into_inner());
+  logger.format = format;
+  logger.filter = filter.clone().unwrap_or_default();
+  filter.map(|_| ())
+}
+
+/// One message of a utils helper, written to the console and to the log files
+pub struct Record<'a> {
+  pub level: Level,
+  pub module: &'a str,
+  pub message: &'a str,
+  /// `("Cause", cause)`, `("Help", help)` or `("Details", details)`
+  pub detail: Option<(&'static str, &'a str)>,
+  /// ANSI color of the pretty output
+  pub color: i32,
+  pub security: bool
+}
+
+pub fn log(record: Record) {
+  let (enabled, format) = {
+    let logger = LOGGER.read().unwrap_or_else(|e| e.into_inner());
+    (record.security || logger.filter.enabled(record.level, record.module), logger.format)
+  };
+  if !enabled { return }
+  write_files(&record);
+
+  let to_stderr = record.level == Level::Error;
+  let format = match format {
+    Format::Auto => {
+      let terminal = if to_stderr { io::stderr().is_terminal() } else { io::stdout().is_terminal() };
+      if terminal { Format::Pretty } else { Format::Plain }
+    }
+    format => format
+  };
+  let line = match format {
+    Format::Json => json_line(&record),
+    Format::Plain => plain_line(&record),
+    _ => pretty_line(&record)
+  };
+
+  // a closed output must not stop the engine
+  let _ = if to_stderr { writeln!(io::stderr(), "{line}") } else { writeln!(io::stdout(), "{line}") };
+}
+
+fn pretty_line(record: &Record) -> String {
+  let mut line = format!(
+    "\x1b[2m({d})\x1b[0m [\x1b[{c}m{module}\x1b[0m]: \x1b[{c}m{message}",
+    d = format_date(Local::now(), "%d/%m/%Y %H:%M:%S"), c = record.color, module = record.module, message = record.message
+  );
+  if let Some((label, value)) = record.detail {
+    line.push_str(&format!("\n                         {label}: {value}"));
+  }
+  line.push_str("\x1b[0m");
+  line
+}
+
+fn plain_line(record: &Record) -> String {
+  let mut line = format!(
+    "({d}) {level:5} [{module}]: {message}",
+    d = format_date(Local::now(), "%d/%m/%Y %H:%M:%S"),
+    level = if record.security { "SECU" } else { record.level.as_str() }.to_uppercase(),
+    module = record.module, message = record.message
+  );
+  if let Some((label, value)) = record.detail {
+    line.push_str(&format!(" ({label}: {value})"));
+  }
+  line
+}
+
+fn json_line(record: &Record) -> String {
+  let mut value = json!({
+    "time": Local::now().to_rfc3339_opts(SecondsFormat::Millis, false),
+    "level": record.level.as_str(),
+    "module": record.module,
+    "message": record.message
+  });
+  if let Some((label, detail)) = record.detail {
+    value[label.to_lowercase()] = json!(detail);
+  }
+  if record.security { value["security"] = json!(true) }
+  value.to_string()
+}
+
+// ==================================
+// files
+
+/// Bounds of the `[logging]` values, checked when the config is loaded
+pub const MAX_LOG_SIZE_MB: u64 = 10_240;
+pub const MAX_KEEP_FILES: usize = 10_000;
+pub const MAX_AGE_DAYS: u64 = 3_650;
+
+/// Log file rotated by size and date, the rotated files are named `mio.2022-12-20.1.log`
+struct RotatingFile {
+  dir: PathBuf,
+  name: String,
+  file: Option<File>,
+  size: u64,
+  /// day of the first line of the current file
+  opened: NaiveDate
+}
+
+struct Files {
+  config: LoggingConfig,
+  main: RotatingFile,
+  security: RotatingFile
+}
+
+static FILES: Lazy<Mutex<Option<Files>>> = Lazy::new(|| Mutex::new(None));
+
+/// Write the messages to the files of `config` too, the security messages go to their own file
+pub fn init_files(config: &LoggingConfig) -> Result<(), String> {
+  let files = if config.enabled {
+    let dir = PathBuf::from(&config.dir);
+    fs::create_dir_all(&dir).map_err(|e| format!("cannot create {}: {e}", config.dir))?;
+    Some(Files {
+      main: RotatingFile::open(&dir, &config.file)?,
+      security: RotatingFile::open(&dir, &config.security_file)?,
+      config: config.clone()
+    })
+  } else { None };
+  *FILES.lock().unwrap_or_else(|e| e.into_inner()) = files;
+  Ok(())
+}
+
+fn write_files(record: &Record) {
+  let mut files = FILES.lock().unwrap_or_else(|e| e.into_inner());
+  let Some(files) = files.as_mut() else { return };
+  let line = if files.config.format == "json" { json_line(record) } else { plain_line(record) };
+  let config = &files.config;
+  let file = if record.security { &mut files.security } else { &mut files.main };
+  file.write(&line, config);
+}
+
+impl RotatingFile {
+  fn open(dir: &Path, name: &str) -> Result<RotatingFile, String> {
+    let path = dir.join(name);
+    let file = append(&path).map_err(|e| format!("cannot open {}: {e}", path.display()))?;
+    let metadata = file.metadata().map_err(|e| e.to_string())?;
+    // a file left by a previous day is rotated before the first line
+    let opened = metadata.modified().map(|t| DateTime::<Local>::from(t).date_naive()).unwrap_or_else(|_| Local::now().date_naive());
+    Ok(RotatingFile { dir: dir.to_path_buf(), name: name.to_string(), file: Some(file), size: metadata.len(), opened })
+  }
+
+  fn write(&mut self, line: &str, config: &LoggingConfig) {
+    let today = Local::now().date_naive();
+    let max_size = config.max_size_mb.saturating_mul(1024 * 1024);
+    let len = line.len() as u64 + 1;
+    if self.size > 0 && ((config.daily && today != self.opened) || (max_size > 0 && self.size + len > max_size)) {
+      // the logger cannot log its own errors
+      if let Err(err) = self.rotate(config) { eprintln!("[Logging]: cannot rotate {}: {err}", self.name) }
+    }
+
+    if let Some(file) = self.file.as_mut() {
+      if writeln!(file, "{line}").is_ok() { self.size += len }
+    }
+  }
+
+  fn rotate(&mut self, config: &LoggingConfig) -> io::Result<()> {
+    self.file = None;
+    let active = self.dir.join(&self.name);
+    let (stem, extension) = split_name(&self.name);
+    let date = self.opened.format("%Y-%m-%d");
+
+    let mut index = 1;
+    let rotated = loop {
+      let candidate = self.dir.join(format!("{stem}.{date}.{index}.{extension}"));
+      if !candidate.exists() && !gz_path(&candidate).exists() { break candidate }
+      index += 1;
+    };
+    let renamed = fs::rename(&active, &rotated);
+
+    self.file = Some(append(&active)?);
+    self.size = 0;
+    self.opened = Local::now().date_naive();
+    renamed?;
+
+    // compressing a big file takes time, the engine keeps logging meanwhile
+    let (dir, name, compress, keep, max_age) = (self.dir.clone(), self.name.clone(), config.compress, config.keep_files, config.max_age_days);
+    thread::spawn(move || {
+      if compress {
+        if let Err(err) = gzip(&rotated) { eprintln!("[Logging]: cannot compress {}: {err}", rotated.display()) }
+      }
+      if let Err(err) = prune(&dir, &name, keep, max_age) { eprintln!("[Logging]: cannot remove the old logs of {name}: {err}") }
+    });
+    Ok(())
+  }
+}
+
+fn append(path: &Path) -> io::Result<File> {
+  OpenOptions::new().create(true).append(true).open(path)
+}
+
+/// `mio.log` → (`mio`, `log`)
+fn split_name(name: &str) -> (&str, &str) {
+  name.rsplit_once('.').unwrap_or((name, "log"))
+}
+
+fn gz_path(path: &Path) -> PathBuf {
+  PathBuf::from(format!("{}.gz", path.display()))
+}
+
+/// Replace `path` by `path.gz`
+fn gzip(path: &Path) -> io::Result<()> {
+  let mut input = File::open(path)?;
+  let mut encoder = GzEncoder::new(File::create(gz_path(path))?, Compression::default());
+  io::copy(&mut input, &mut encoder)?;
+  encoder.finish()?;
+  fs::remove_file(path)
+}
+
+/// Whether `file_name` is a rotated file of `name`: `mio.2022-12-20.1.log` or `mio.2022-12-20.1.log.gz` for `mio.log`.
+/// The shape is matched exactly so `mio.security.log` is not taken for a rotated file of `mio.log`
+fn is_rotated(file_name: &str, name: &str) -> bool {
+  let (stem, extension) = split_name(name);
+  let rest = file_name.strip_prefix(stem).and_then(|r| r.strip_prefix('.'));
+  let rest = rest.map(|r| r.strip_suffix(".gz").unwrap_or(r)).and_then(|r| r.strip_suffix(extension)).and_then(|r| r.strip_suffix('.'));
+  let Some((date, index)) = rest.and_then(|r| r.split_once('.')) else { return false };
+  date.len() == 10 && NaiveDate::parse_from_str(date, "%Y-%m-%d").is_ok()
+    && !index.is_empty() && index.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Remove the rotated files of `name` beyond the `keep` newest ones or older than `max_age_days`
+fn prune(dir: &Path, name: &str, keep: usize, max_age_days: u64) -> io::Result<()> {
+  let mut rotated: Vec<(SystemTime, PathBuf)> = fs::read_dir(dir)?
+    .filter_map(|entry| entry.ok())
+    .filter(|entry| is_rotated(&entry.file_name().to_string_lossy(), name))
+    .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+    .collect();
+  rotated.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+
+  let max_age = Duration::from_secs(max_age_days.saturating_mul(24 * 60 * 60));
+  for (i, (modified, path)) in rotated.iter().enumerate() {
+    let too_many = keep > 0 && i >= keep;
+    let too_old = max_age_days > 0 && modified.elapsed().map(|age| age > max_age).unwrap_or(false);
+    if too_many || too_old { fs::remove_file(path)? }
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::Read;
+  use flate2::read::GzDecoder;
+
+  /// Empty directory of the system temp dir, unique to the test
+  fn temp_dir(test: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("mio-logs-{}-{test}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+  }
+
+  fn files(dir: &Path) -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(dir).unwrap().map(|e| e.unwrap().file_name().to_string_lossy().to_string()).collect();
+    names.sort();
+    names
+  }
+
+  fn touch(dir: &Path, name: &str, age: Duration) {
+    File::create(dir.join(name)).unwrap().set_modified(SystemTime::now() - age).unwrap();
+  }
+
+  fn config() -> LoggingConfig {
+    LoggingConfig { max_size_mb: 0, daily: false, compress: false, keep_files: 0, max_age_days: 0, ..Default::default() }
+  }
+
+  const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+  #[test]
+  fn rotated_file_names() {
+    assert!(is_rotated("mio.2022-12-20.1.log", "mio.log"));
+    assert!(is_rotated("mio.2022-12-20.12.log.gz", "mio.log"));
+    assert!(is_rotated("mio.security.2022-12-20.1.log", "mio.security.log"));
+
+    assert!(!is_rotated("mio.log", "mio.log"));
+    assert!(!is_rotated("mio.security.log", "mio.log"));
+    assert!(!is_rotated("mio.security.2022-12-20.1.log", "mio.log"));
+    assert!(!is_rotated("mio.2022-12-20.log", "mio.log"));
+    assert!(!is_rotated("mio.2022-13-20.1.log", "mio.log"));
+    assert!(!is_rotated("mio.2022-12-20.x.log", "mio.log"));
+    assert!(!is_rotated("mio.2022-12-20.1.txt", "mio.log"));
+    assert!(!is_rotated("mio2.2022-12-20.1.log", "mio.log"));
+  }
+
+  #[test]
+  fn rotation_by_size() {
+    let dir = temp_dir("size");
+    let config = LoggingConfig { max_size_mb: 1, ..config() };
+    let line = "x".repeat(600 * 1024);
+
+    let mut file = RotatingFile::open(&dir, "mio.log").unwrap();
+    file.write(&line, &config);
+    assert_eq!(files(&dir), ["mio.log"]);
+    file.write(&line, &config);
+
+    let rotated = format!("mio.{}.1.log", Local::now().format("%Y-%m-%d"));
+    assert_eq!(files(&dir), [rotated.as_str(), "mio.log"]);
+    assert_eq!(fs::metadata(dir.join(&rotated)).unwrap().len(), line.len() as u64 + 1);
+    assert_eq!(fs::metadata(dir.join("mio.log")).unwrap().len(), line.len() as u64 + 1);
+    fs::remove_dir_all(&dir).ok();
+  }
+
+  #[test]
+  fn daily_rotation_uses_the_next_free_index() {
+    let dir = temp_dir("daily");
+    let config = LoggingConfig { daily: true, ..config() };
+    let yesterday = Local::now().date_naive().pred_opt().unwrap();
+
+    let mut file = RotatingFile::open(&dir, "mio.log").unwrap();
+    file.write("first", &config);
+    for line in ["second", "third"] {
+      file.opened = yesterday;
+      file.write(line, &config);
+    }
+
+    let date = yesterday.format("%Y-%m-%d");
+    let read = |name: String| fs::read_to_string(dir.join(name)).unwrap();
+    assert_eq!(files(&dir).len(), 3);
+    assert_eq!(read(format!("mio.{date}.1.log")), "first\n");
+    assert_eq!(read(format!("mio.{date}.2.log")), "second\n");
+    assert_eq!(read("mio.log".to_string()), "third\n");
+    fs::remove_dir_all(&dir).ok();
+  }
+
+  #[test]
+  fn rotated_files_are_compressed() {
+    let dir = temp_dir("gzip");
+    let path = dir.join("mio.2022-12-20.1.log");
+    fs::write(&path, "some lines\n").unwrap();
+
+    gzip(&path).unwrap();
+    assert_eq!(files(&dir), ["mio.2022-12-20.1.log.gz"]);
+    let mut content = String::new();
+    GzDecoder::new(File::open(gz_path(&path)).unwrap()).read_to_string(&mut content).unwrap();
+    assert_eq!(content, "some lines\n");
+    fs::remove_dir_all(&dir).ok();
+  }
+
+  #[test]
+  fn prune_only_removes_the_files_of_the_stream() {
+    let dir = temp_dir("prune");
+    touch(&dir, "mio.log", Duration::ZERO);
+    touch(&dir, "mio.2022-12-18.1.log.gz", 3 * DAY);
+    touch(&dir, "mio.2022-12-19.1.log.gz", 2 * DAY);
+    touch(&dir, "mio.2022-12-20.1.log", DAY / 2);
+    touch(&dir, "mio.security.log", Duration::ZERO);
+    touch(&dir, "mio.security.2022-12-17.1.log", 10 * DAY);
+    touch(&dir, "mio.old.log", 10 * DAY);
+
+    // the newest files are kept
+    prune(&dir, "mio.log", 2, 0).unwrap();
+    assert!(!dir.join("mio.2022-12-18.1.log.gz").exists());
+    assert_eq!(files(&dir).len(), 6);
+
+    // then the old ones are removed
+    prune(&dir, "mio.log", 0, 1).unwrap();
+    assert_eq!(files(&dir), ["mio.2022-12-20.1.log", "mio.log", "mio.old.log", "mio.security.2022-12-17.1.log", "mio.security.log"]);
+
+    prune(&dir, "mio.security.log", 0, 1).unwrap();
+    assert_eq!(files(&dir), ["mio.2022-12-20.1.log", "mio.log", "mio.old.log", "mio.security.log"]);
+    fs::remove_dir_all(&dir).ok();
+  }
+}
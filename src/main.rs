@@ -1,11 +1,19 @@
 /*  DECLARE MODULES */
 pub mod utils;
 mod init;
+#[cfg(feature = "private")]
 mod constants;
 mod handle;
 mod storage;
 mod libs;
 mod assets;
+mod maintenance;
+mod status;
+mod latency;
+mod shutdown;
+mod reload;
+mod cli;
+mod logging;
 
 
 extern crate serde_derive;
@@ -14,43 +22,44 @@ extern crate rust_i18n;
 /* import */
 use std::{
     sync::Arc,
-    time::Duration,
-    collections::HashMap, process::exit
+    path::Path,
+    process::exit,
+    io::{ stdout, IsTerminal }
 };
+use clap::Parser;
+use sqlx::{ MySqlConnection, migrate::Migrator };
 use chrono::Utc;
 use libs::i18n;
 //use mysql_async::prelude::Queryable;
 use serenity::{
     async_trait,
-    model::{ channel::Message, gateway::Ready, prelude::Activity  },
-    prelude::*,
-    client::bridge::gateway::ShardManager
-};
-use storage::{ Latency, Status, ClientActivityType };
-use tokio::{
-    signal,
-    time::sleep
+    model::{
+        channel::{ GuildChannel, Message }, gateway::Ready, application::interaction::Interaction,
+        guild::{ Guild, Member, UnavailableGuild }, id::GuildId, user::User
+    },
+    prelude::*
 };
+use storage::{ Database, GuildCounts };
+use handle::{ prefixes::GuildPrefixes, cooldowns::Cooldowns, hooks::CommandUsage };
 
 use crate::{
-    constants::{check_comp_id, ARCHIVE_DIR},
-    init::Config,
-    storage::Storage, libs::security::archive::{ self, Archive }
+    cli::{ Cli, Command, ConfigAction, ArchiveAction, MaintenanceAction, DbAction, I18nAction, MIGRATIONS_DIR },
+    init::{ Config, ConfigOptions, LoadedConfig },
+    maintenance::Maintenance,
+    status::StatusManager,
+    reload::ConfigState,
+    storage::Storage
 };
+#[cfg(feature = "private")]
+use crate::{ constants::{ check_comp_id, ARCHIVE_DIR }, libs::security::archive::{ self, Archive } };
 
 
-struct ShardManagerContainer;
-
-impl TypeMapKey for ShardManagerContainer {
-    type Value = Arc<RwLock<ShardManager>>;
-}
-
 struct Handler;
 
 #[async_trait]
 impl EventHandler for Handler {
     async fn message(&self, ctx: Context, msg: Message){
-        if msg.author.bot || msg.content.len() < 1 { return; };
+        if msg.author.bot || msg.content.is_empty() { return; };
         
         let storage_lock = {
             let data = ctx.data.read().await;
@@ -58,12 +67,24 @@ impl EventHandler for Handler {
         };
         let storage = storage_lock.read().await;
 
-        handle::commands::execute(&ctx, &ctx.http, &msg, &storage).await;
+        handle::commands::execute(&ctx, &msg, &storage).await;
 
         drop(storage);
         drop(storage_lock);
     }
 
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        if let Interaction::ApplicationCommand(command) = interaction {
+            let storage_lock = {
+                let data = ctx.data.read().await;
+                data.get::<Storage>().expect("Expected Storage in TypeMap.").clone()
+            };
+            let storage = storage_lock.read().await;
+
+            handle::interactions::execute(&ctx, &command, &storage).await;
+        }
+    }
+
     async fn ready(&self, ctx: Context, ready: Ready){
         utils::success("Ready", format!("{} is ready", ready.user.name).as_str());
 
@@ -71,7 +92,24 @@ impl EventHandler for Handler {
             let data = ctx.data.read().await;
             data.get::<Storage>().expect("Expected Storage in TypeMap.").clone()
         };
-        let storage = storage_lock.read().await;
+        let mut storage = storage_lock.write().await;
+        storage.client.id = Some(ready.user.id);
+        for guild in ready.guilds.iter() {
+            storage.client.guilds.entry(guild.id).or_default();
+        }
+
+        match ctx.http.get_current_application_info().await {
+            Ok(app) => {
+                let mut owners = vec![app.owner.id];
+                if let Some(team) = app.team {
+                    owners.extend(team.members.iter().map(|m| m.user.id));
+                }
+                for owner in owners {
+                    if !storage.client.owners.contains(&owner) { storage.client.owners.push(owner); }
+                }
+            },
+            Err(err) => utils::warn_with_cause("Ready", "cannot fetch the application owners", err.to_string().as_str())
+        }
 
         let now = Utc::now();
         let start_time = now.timestamp_millis() - storage.process_start.timestamp_millis();
@@ -79,19 +117,244 @@ impl EventHandler for Handler {
             "MioEngine",
             format!("Process started in {}ms", start_time).as_str()
         );
+
+        let slash_guilds = storage.client.slash_guilds.clone();
+        drop(storage);
+        handle::interactions::sync(&ctx.http, &slash_guilds).await;
+    }
+
+    // guild, member and channel counts, shown by the statuses and the stats
+
+    async fn guild_create(&self, ctx: Context, guild: Guild) {
+        let storage_lock = storage_of(&ctx).await;
+        let counts = GuildCounts { members: guild.member_count, channels: guild.channels.len() as u64 };
+        storage_lock.write().await.client.guilds.insert(guild.id, counts);
+    }
+
+    async fn guild_delete(&self, ctx: Context, incomplete: UnavailableGuild) {
+        // an unavailable guild is an outage, the client is still in it
+        if incomplete.unavailable { return; }
+        let storage_lock = storage_of(&ctx).await;
+        storage_lock.write().await.client.guilds.remove(&incomplete.id);
+    }
+
+    async fn guild_member_addition(&self, ctx: Context, new_member: Member) {
+        let storage_lock = storage_of(&ctx).await;
+        let mut storage = storage_lock.write().await;
+        if let Some(counts) = storage.client.guilds.get_mut(&new_member.guild_id) { counts.members += 1; }
+    }
+
+    async fn guild_member_removal(&self, ctx: Context, guild_id: GuildId, _kicked: User) {
+        let storage_lock = storage_of(&ctx).await;
+        let mut storage = storage_lock.write().await;
+        if let Some(counts) = storage.client.guilds.get_mut(&guild_id) { counts.members = counts.members.saturating_sub(1); }
+    }
+
+    async fn channel_create(&self, ctx: Context, channel: &GuildChannel) {
+        let storage_lock = storage_of(&ctx).await;
+        let mut storage = storage_lock.write().await;
+        if let Some(counts) = storage.client.guilds.get_mut(&channel.guild_id) { counts.channels += 1; }
     }
+
+    async fn channel_delete(&self, ctx: Context, channel: &GuildChannel) {
+        let storage_lock = storage_of(&ctx).await;
+        let mut storage = storage_lock.write().await;
+        if let Some(counts) = storage.client.guilds.get_mut(&channel.guild_id) { counts.channels = counts.channels.saturating_sub(1); }
+    }
+}
+
+async fn storage_of(ctx: &Context) -> Arc<RwLock<Storage>> {
+    let data = ctx.data.read().await;
+    data.get::<Storage>().expect("Expected Storage in TypeMap.").clone()
 }
 
-fn get_config() -> Config {
-    match init::read_config() {
+fn get_config(options: &ConfigOptions) -> LoadedConfig {
+    match init::read_config(options) {
         Ok(cnf) => cnf,
-        Err(_) => panic!("Cannot resolve configuration, exit.")
+        Err(problems) => {
+            for problem in problems.iter() {
+                utils::error_without_cause("ConfigReader", problem.to_string().as_str());
+            }
+            utils::error("ConfigReader", format!("{} problem.s found in the configuration", problems.len()).as_str(), "exit code 6");
+            exit(6)
+        }
+    }
+}
+
+/// `config show`: effective config and the source of each value
+fn show_config(options: &ConfigOptions) {
+    let loaded = get_config(options);
+    let lines = loaded.describe();
+    let width = lines.iter().map(|(key, _, _)| key.len()).max().unwrap_or(0);
+    // the sources are dimmed in a terminal only, not when the output is piped
+    let (dim, reset) = if stdout().is_terminal() { ("\x1b[2m", "\x1b[0m") } else { ("", "") };
+    for (key, value, source) in lines {
+        println!("{key:width$} = {value}  {dim}({}){reset}", source.describe());
+    }
+}
+
+/// The private modules are not published, the commands which need them cannot run without the `private` feature
+#[cfg(not(feature = "private"))]
+fn without_private_modules<T>(command: &str) -> T {
+    utils::error(
+        "MioEngine",
+        format!("`{command}` needs the token and the credentials of the private modules").as_str(),
+        "built without the `private` feature, exit code 2"
+    );
+    exit(2)
+}
+
+/// MEFS archive, rewritten when it is invalid and `rewrite_archive_if_invalid` is enabled
+#[cfg(feature = "private")]
+fn load_archive(config: &Config) -> Archive {
+    let arch = archive::Archive::from_file(
+        &ARCHIVE_DIR.to_string(),
+        config.client.version.clone(),
+        config.security.rewrite_archive_if_invalid.clone(),
+        config.security.auto_save_archive.clone()
+    );
+    match arch {
+        Ok(a) => a,
+        Err(err) => {
+            utils::error("ArchiveSystem", "cannot load archive", err.as_str());
+            if config.security.rewrite_archive_if_invalid {
+                archive::Archive::new(
+                    &ARCHIVE_DIR.to_string(),
+                    config.client.version.clone(),
+                    config.security.rewrite_archive_if_invalid.clone(),
+                    config.security.auto_save_archive.clone()
+                )
+            } else {
+                utils::error("ArchiveSystem", "parameter `rewrite_archive_if_invalid` was disabled", "exit code 2");
+                exit(2)
+            }
+        }
+    }
+}
+
+/// `check-config`: exit code 0 when the config can be used
+fn check_config(options: &ConfigOptions) {
+    let loaded = get_config(options);
+    let given = loaded.values.len();
+    utils::success("ConfigReader", format!("The configuration is valid ({given} key.s given, the others use their default value)").as_str());
+}
+
+/// `archive get|set`
+#[cfg(feature = "private")]
+fn archive_command(options: &ConfigOptions, action: ArchiveAction) {
+    let config = get_config(options).config;
+    let mut archive = load_archive(&config);
+    match action {
+        ArchiveAction::Get { section, key } => {
+            let value = archive.get(section.as_str(), key.as_str());
+            if value.is_null() {
+                utils::warn("ArchiveSystem", format!("`{section}.{key}` is not defined").as_str());
+                exit(1)
+            }
+            println!("{value}");
+        },
+        ArchiveAction::Set { section, key, value } => {
+            let value = serde_json::from_str::<serde_json::Value>(&value).unwrap_or(serde_json::Value::String(value));
+            if let Err(err) = archive.set(section.as_str(), key.as_str(), value) {
+                utils::error("ArchiveSystem", format!("cannot set `{section}.{key}`").as_str(), err.as_str());
+                exit(2)
+            }
+            if let Err(err) = archive.save() {
+                utils::error("ArchiveSystem", "cannot save the archive", err.as_str());
+                exit(2)
+            }
+            utils::security("ArchiveSystem", format!("`{section}.{key}` edited from the command line").as_str());
+        }
+    }
+}
+
+#[cfg(not(feature = "private"))]
+fn archive_command(_options: &ConfigOptions, _action: ArchiveAction) {
+    without_private_modules("archive")
+}
+
+/// `maintenance on|off`: write the maintenance file, the running instances watch it
+fn maintenance_command(action: MaintenanceAction) {
+    let mut maintenance = match Maintenance::load() {
+        Ok(state) => state,
+        Err(err) => {
+            utils::error("Maintenance", "cannot read the maintenance state", err.as_str());
+            exit(2)
+        }
+    };
+    match action {
+        MaintenanceAction::On { eta, reason } => {
+            maintenance.enable(reason, eta.map(|eta| Utc::now().timestamp() + eta.as_secs() as i64));
+        },
+        MaintenanceAction::Off => maintenance.disable()
     }
+    if let Err(err) = maintenance.save() {
+        utils::error("Maintenance", "cannot save the maintenance state", err.as_str());
+        exit(2)
+    }
+    let state = if maintenance.enabled { "enabled" } else { "disabled" };
+    utils::success("Maintenance", format!("Maintenance mode {state} from the command line").as_str());
+}
+
+/// `db migrate`: apply the migrations of `dir` which are not applied yet
+async fn migrate(options: &ConfigOptions, dir: &str) {
+    let config = get_config(options).config;
+    let mut conn = connect_database(&config).await;
+    apply_migrations(&mut conn, dir).await;
+}
+
+/// Connection to the database of the archive
+#[cfg(feature = "private")]
+async fn connect_database(config: &Config) -> MySqlConnection {
+    libs::database::create_database(&load_archive(config)).await
+}
+
+#[cfg(not(feature = "private"))]
+async fn connect_database(_config: &Config) -> MySqlConnection {
+    without_private_modules("db migrate")
+}
+
+/// Apply the migrations of `dir` which are not applied yet, the engine cannot run on an outdated schema
+async fn apply_migrations(conn: &mut MySqlConnection, dir: &str) {
+    let migrator = match Migrator::new(Path::new(dir)).await {
+        Ok(migrator) => migrator,
+        Err(err) => {
+            utils::error("Migrations", format!("cannot read the migrations of {dir}").as_str(), err.to_string().as_str());
+            exit(3)
+        }
+    };
+    match migrator.run(conn).await {
+        Ok(()) => utils::success("Migrations", format!("The database is up to date ({} migration.s)", migrator.iter().count()).as_str()),
+        Err(err) => {
+            utils::error("Migrations", "cannot apply the migrations", err.to_string().as_str());
+            exit(3)
+        }
+    }
+}
+
+/// `i18n check`: every language has the keys and placeholders of the default one
+async fn i18n_check(options: &ConfigOptions) {
+    let config = get_config(options).config;
+    i18n::load(&config.i18n.locales_dir).await;
+    let problems = i18n::check().await;
+    if problems.is_empty() {
+        utils::success("LanguageHandler", "Every language is complete");
+        return
+    }
+    for problem in problems.iter() {
+        utils::warn("LanguageHandler", problem.as_str());
+    }
+    utils::error("LanguageHandler", format!("{} problem.s found in the languages", problems.len()).as_str(), "exit code 1");
+    exit(1)
 }
 
 async fn build_client() -> Client {
-    let intents = GatewayIntents::GUILD_MESSAGES | GatewayIntents::GUILD_MEMBERS | GatewayIntents::DIRECT_MESSAGES | GatewayIntents::MESSAGE_CONTENT;
-    match Client::builder(&constants::TOKEN, intents).event_handler(Handler).await {
+    let intents = GatewayIntents::GUILDS | GatewayIntents::GUILD_MESSAGES | GatewayIntents::GUILD_MEMBERS | GatewayIntents::DIRECT_MESSAGES | GatewayIntents::MESSAGE_CONTENT;
+    #[cfg(feature = "private")]
+    let token = &constants::TOKEN;
+    #[cfg(not(feature = "private"))]
+    let token: String = without_private_modules("run");
+    match Client::builder(token, intents).event_handler(Handler).await {
         Ok(client) => client,
         Err(err) => {
             utils::error("ClientBuilder", "cannot initialize client", err.to_string().as_str());
@@ -107,16 +370,44 @@ fn copyright(){
     }
 }
 
+/// The company id is one of the private constants, the public build always shows the banner
+#[cfg(not(feature = "private"))]
+fn check_comp_id() -> bool {
+    false
+}
+
 #[tokio::main]
 async fn main() {
+    let cli = Cli::parse();
+    if let Err(err) = logging::init(cli.global.log_level.as_deref(), cli.global.log_format) {
+        utils::error("Logging", "invalid MIO_LOG filter, info is used", err.as_str());
+    }
+    let options = cli.global.config_options();
+
+    match cli.command.unwrap_or(Command::Run) {
+        Command::Run => run(options, !cli.global.no_banner).await,
+        Command::CheckConfig => check_config(&options),
+        Command::Config { action: ConfigAction::Show } => show_config(&options),
+        Command::Archive { action } => archive_command(&options, action),
+        Command::Maintenance { action } => maintenance_command(action),
+        Command::Db { action: DbAction::Migrate { dir } } => migrate(&options, &dir).await,
+        Command::I18n { action: I18nAction::Check } => i18n_check(&options).await
+    }
+}
 
+async fn run(options: ConfigOptions, banner: bool) {
     // Mio Engine
-    copyright();
+    if banner { copyright(); }
     utils::info("MioEngine", "initialisation...");
 
     // CONFIG 
 
-    let config = get_config();
+    let loaded = get_config(&options);
+    let config_state = ConfigState::new(options, &loaded);
+    let config = loaded.config;
+    if let Err(err) = logging::init_files(&config.logging) {
+        utils::error("Logging", "cannot open the log files, only the console is used", err.as_str());
+    }
     if config.client.dev {
         std::env::set_var("RUST_BACKTRACE", "1");
         utils::info("DevMode", "This instance is initialised as in-dev.");
@@ -126,32 +417,8 @@ async fn main() {
     }
 
     // MEFS
-    #[allow(unused_mut)]
-    let mut archive = {
-        let arch = archive::Archive::from_file(
-            &ARCHIVE_DIR.to_string(),
-            config.client.version.clone(),
-            config.security.rewrite_archive_if_invalid.clone(),
-            config.security.auto_save_archive.clone()
-        );
-        match arch {
-            Ok(a) => a,
-            Err(err) => {
-                utils::error("ArchiveSystem", "cannot load archive", err.as_str());
-                if config.security.rewrite_archive_if_invalid {
-                    archive::Archive::new(
-                        &ARCHIVE_DIR.to_string(),
-                        config.client.version.clone(),
-                        config.security.rewrite_archive_if_invalid.clone(),
-                        config.security.auto_save_archive.clone()
-                    )
-                } else {
-                    utils::error("ArchiveSystem", "parameter `rewrite_archive_if_invalid` was disabled", "exit code 2");
-                    exit(2)
-                }
-            }
-        }
-    };
+    #[cfg(feature = "private")]
+    let archive = load_archive(&config);
     
     //let db_enc_key_temp = archive.get("DatabaseConnectionHandler", "db_key");
     //let db_enc_key = if db_enc_key_temp.is_null() { generate_encryption_key(512) } else { db_enc_key_temp.to_string() };
@@ -164,7 +431,10 @@ async fn main() {
     //let _ = archive.set("DatabaseConnectionLogging", "db_key", db_enc_key);
 
     utils::info("MioEngine", "loading mysql instance...");
+    #[cfg(feature = "private")]
     let mut conn = libs::database::create_database(&archive).await;
+    #[cfg(not(feature = "private"))]
+    let mut conn: MySqlConnection = without_private_modules("run");
     
     let _q = sqlx::query("SELECT * FROM test WHERE id < 2;")
         .fetch_one(&mut conn)
@@ -173,127 +443,70 @@ async fn main() {
     
     //dbg!(&q.get::<i32, _>("id"), &1);
 
+    apply_migrations(&mut conn, MIGRATIONS_DIR).await;
+    let guild_prefixes = match GuildPrefixes::load(&mut conn).await {
+        Ok(prefixes) => prefixes,
+        Err(err) => {
+            utils::error("GuildPrefixes", "cannot load the guild prefixes", err.as_str());
+            exit(3)
+        }
+    };
+
     i18n::load(&config.i18n.locales_dir).await;
     i18n::test().await;
 
 
+    let maintenance = match Maintenance::load() {
+        Ok(state) => {
+            if state.enabled { utils::warn("Maintenance", "This instance starts in maintenance mode"); }
+            state
+        },
+        Err(err) => {
+            utils::error("Maintenance", "cannot read the maintenance state, maintenance disabled", err.as_str());
+            Maintenance::default()
+        }
+    };
+
     let mut client = build_client().await;
 
     let stock: Storage = Storage::new(&config);
+    let status_manager = StatusManager::new(&config);
     {
         let mut data = client.data.write().await;
         data.insert::<Storage>(Arc::new(RwLock::new(stock)));
+        #[cfg(feature = "private")]
         data.insert::<Archive>(Arc::new(RwLock::new(archive)));
+        data.insert::<Database>(Arc::new(Mutex::new(conn)));
+        data.insert::<GuildPrefixes>(Arc::new(RwLock::new(guild_prefixes)));
+        data.insert::<Cooldowns>(Arc::new(Mutex::new(Cooldowns::new())));
+        data.insert::<Maintenance>(Arc::new(RwLock::new(maintenance)));
+        data.insert::<StatusManager>(Arc::new(RwLock::new(status_manager)));
+        data.insert::<CommandUsage>(Arc::new(Mutex::new(CommandUsage::default())));
+        data.insert::<ConfigState>(Arc::new(Mutex::new(config_state)));
         drop(data);
     }
+    handle::hooks::register_builtins().await;
 
     // shard listener && ping manager
-    let manager_copy = client.shard_manager.clone();
-    let arc_client_data = client.data.clone();
-    tokio::spawn(async move {
-        let mut shards: HashMap<u64, Latency> = HashMap::new();
-        loop {
-            sleep(Duration::from_secs(10)).await;
-    
-            // manage all shards and free the ShardManager after
-            {
-                let lock = manager_copy.lock().await;
-                let shard_runners = lock.runners.lock().await;
-                for (id, runner) in shard_runners.iter() {
-                    let ping = runner.latency.unwrap_or(Duration::from_millis(0));
-                    // save every latency
-                    if let Some(s) = shards.get_mut(&id.0) { s.ping = ping }
-                    else { shards.insert(id.0, Latency { ping, warned: false }); };
-                    
-                    // warn if the ping is to high
-                    if let Some(s) = shards.get_mut(&id.0) {
-                        if ping.as_millis() > constants::SHARD_PING_WARN_MIN && !s.warned {
-                            // AYO
-                            utils::warn("ShardLatency", format!("The shard {} have a latency of {}ms, the ping is to high and may cause user-side latency", id.0, ping.as_millis()).as_str());
-                            s.warned = true;
-                            runner.runner_tx.set_status(serenity::model::user::OnlineStatus::DoNotDisturb);
-                        } else {
-                            if s.warned &&  ping.as_millis() < constants::SHARD_PING_WARN_MIN {
-                                utils::info("ShardLatency", format!("The shard {} have a latency of {}ms, the ping is now normal", id.0, ping.as_millis()).as_str());
-                                s.warned = false;
-                                runner.runner_tx.set_status(serenity::model::user::OnlineStatus::Online);
-                            }
-                        }
-                    }
-                };
-            }
+    tokio::spawn(latency::monitor(
+        client.data.clone(),
+        client.shard_manager.clone(),
+        client.cache_and_http.http.clone(),
+        config.latency.clone(),
+        config.watchdog.clone()
+    ));
 
+    // maintenance state edited from outside (admin CLI)
+    tokio::spawn(maintenance::watch(client.data.clone()));
 
-            {
-                let client_data = arc_client_data.write().await;
-            
-                // stock new latency in the Storage instance
-                let storage_lock = client_data.get::<Storage>().expect("Expected Storage in TypeMap").clone();
-                let mut storage = storage_lock.write().await;
-                storage.latency = shards.clone();
-            }
-        }
-    });
-
-    // sigint handler
-    let shard_manager = client.shard_manager.clone();
-    tokio::spawn(async move {
-        match signal::ctrl_c().await {
-            Ok(()) => {
-                println!("\n");
-                utils::info("MioEngine", "Exit Signal received");
-                let mut shards = shard_manager.lock().await;
-                utils::info("MioEngine", format!("Shutting down all shards... ({} shard.s)", shards.shards_instantiated().await.len()).as_str());
-                shards.shutdown_all().await;
-                utils::success("MioEngine", "All shards have been killed");
-                utils::success("MioEngine", "Exit code 0");
-                std::process::exit(0);
-            },
-            Err(err) => {
-                eprintln!("Unable to listen for shutdown signal: {}", err);
-                std::process::exit(1);
-            },
-        };
-    });
+    // config.toml edited or SIGHUP
+    tokio::spawn(reload::watch(client.data.clone()));
 
-    // status
-    let manager_copy = client.shard_manager.clone();
-    let arc_client_data = client.data.clone();
-    let status_time = Duration::from_secs(config.params.status_time.try_into().unwrap());
-    tokio::spawn(async move {
-        let mut status_index = 0;
-        loop {
-            sleep(status_time).await;
-            
-            let client_data = arc_client_data.read().await;
-            let lock = manager_copy.lock().await;
-            let shard_runners = lock.runners.lock().await;
-
-            let storage_lock = client_data.get::<Storage>().expect("Expected Storage in TypeMap");
-            let storage = storage_lock.read().await;
+    // SIGINT / SIGTERM: ordered shutdown hooks
+    tokio::spawn(shutdown::Coordinator::new().listen(client.data.clone(), client.shard_manager.clone()));
 
-            {
-                status_index = (status_index + 1) % storage.status.list.len();
-
-                let new_state = if storage.dev { storage.status.dev_status.clone() }
-                    else if storage.debug { storage.status.debug_mode_status.clone() }
-                    else if storage.maintenance { storage.status.maintenance_status.clone() }
-                    else { storage.status.list.get(status_index).unwrap_or(&Status { message: "👋".to_string(), status_type: storage::ClientActivityType::Watching }).clone() };
-                    
-                for (_id, runner) in shard_runners.iter() {
-                    // for every shards
-                    match new_state.status_type {
-                        ClientActivityType::Playing => { runner.runner_tx.set_activity(Some(Activity::playing(new_state.message.clone()))); }
-                        ClientActivityType::Watching => { runner.runner_tx.set_activity(Some(Activity::watching(new_state.message.clone()))); }
-                        ClientActivityType::Listening => { runner.runner_tx.set_activity(Some(Activity::listening(new_state.message.clone()))); }
-                        ClientActivityType::Streaming => { runner.runner_tx.set_activity(Some(Activity::streaming(new_state.message.clone(), storage.status.streaming_url.clone()))); }
-                        ClientActivityType::Unknown => {}
-                    };
-                };
-                if new_state.status_type.is_unknown() { utils::warn("StatusLoop", "Status type was unknown"); }
-            }
-        }
-    });
+    // status
+    tokio::spawn(status::run(client.data.clone(), client.shard_manager.clone()));
 
     
     // before login time trace
@@ -0,0 +1,106 @@
+use std::{ fs, sync::Arc, time::{ Duration, SystemTime } };
+use serde::{ Deserialize, Serialize };
+use serenity::prelude::{ TypeMapKey, RwLock, TypeMap };
+use tokio::time::sleep;
+
+use crate::utils;
+
+pub const MAINTENANCE_FILE: &str = "./maintenance.json";
+
+/// Maintenance mode, persisted in [MAINTENANCE_FILE] so it survives restarts and can be edited by the admin CLI
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Maintenance {
+  pub enabled: bool,
+  pub reason: Option<String>,
+  /// estimated end of the maintenance, unix timestamp in seconds
+  pub eta: Option<i64>,
+  /// commands still available to everyone
+  #[serde(default)]
+  pub commands: Vec<String>,
+  #[serde(default)]
+  pub users: Vec<u64>,
+  #[serde(default)]
+  pub guilds: Vec<u64>,
+  /// last modification of the file known by this instance
+  #[serde(skip)]
+  modified: Option<SystemTime>
+}
+
+impl TypeMapKey for Maintenance {
+  type Value = Arc<RwLock<Maintenance>>;
+}
+
+impl Maintenance {
+  /// Read the persisted state, a missing file means no maintenance
+  pub fn load() -> Result<Self, String> {
+    let content = match fs::read_to_string(MAINTENANCE_FILE) {
+      Ok(content) => content,
+      Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Maintenance::default()),
+      Err(err) => return Err(err.to_string())
+    };
+    let mut state: Maintenance = serde_json::from_str(&content).map_err(|e| e.to_string())?;
+    state.modified = modified_at();
+    Ok(state)
+  }
+
+  pub fn save(&mut self) -> Result<(), String> {
+    let content = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+    fs::write(MAINTENANCE_FILE, content).map_err(|e| e.to_string())?;
+    self.modified = modified_at();
+    Ok(())
+  }
+
+  pub fn enable(&mut self, reason: Option<String>, eta: Option<i64>) {
+    self.enabled = true;
+    self.reason = reason;
+    self.eta = eta;
+  }
+
+  pub fn disable(&mut self) {
+    self.enabled = false;
+    self.reason = None;
+    self.eta = None;
+  }
+
+  /// Whether a command can run despite the maintenance, owners are checked by the caller
+  pub fn allows(&self, command: &str, user_id: u64, guild_id: Option<u64>) -> bool {
+    !self.enabled
+      || self.commands.iter().any(|c| c == command)
+      || self.users.contains(&user_id)
+      || guild_id.map(|g| self.guilds.contains(&g)).unwrap_or(false)
+  }
+}
+
+fn modified_at() -> Option<SystemTime> {
+  fs::metadata(MAINTENANCE_FILE).and_then(|m| m.modified()).ok()
+}
+
+/// Reload the state when the file is changed by someone else (the admin CLI for instance)
+pub async fn watch(data: Arc<RwLock<TypeMap>>) {
+  loop {
+    sleep(Duration::from_secs(5)).await;
+
+    let maintenance_lock = {
+      let data = data.read().await;
+      data.get::<Maintenance>().expect("Expected Maintenance in TypeMap.").clone()
+    };
+
+    let known = maintenance_lock.read().await.modified;
+    let current = modified_at();
+    if current.is_none() || current == known { continue; }
+
+    match Maintenance::load() {
+      Ok(state) => {
+        let mut maintenance = maintenance_lock.write().await;
+        if state.enabled != maintenance.enabled {
+          utils::info("Maintenance", format!("Maintenance mode {} from {}", if state.enabled { "enabled" } else { "disabled" }, MAINTENANCE_FILE).as_str());
+        }
+        *maintenance = state;
+      },
+      Err(err) => {
+        utils::error("Maintenance", "cannot reload the maintenance state", err.as_str());
+        maintenance_lock.write().await.modified = current;
+      }
+    }
+  }
+}
\ No newline at end of file
@@ -0,0 +1,248 @@
+use std::{ collections::BTreeMap, fs, sync::Arc, time::{ Duration, SystemTime } };
+use serenity::prelude::{ TypeMapKey, Mutex, RwLock, TypeMap };
+use tokio::time::sleep;
+
+use crate::{ utils, init::{ self, Config, ConfigOptions, LoadedConfig }, storage::Storage, status::StatusManager, libs::i18n };
+
+/// Keys applied without a restart, a key also covers its sub-keys
+pub const RELOADABLE_KEYS: [&str; 12] = [
+  "client.dev",
+  "params.prefix",
+  "params.case_insensitive_commands",
+  "params.status",
+  "params.status_time",
+  "params.status_mode",
+  "params.auto_status",
+  "params.dev_status",
+  "params.maintenance_status",
+  "params.debug_status",
+  "params.streaming_url",
+  "i18n.locales_dir"
+];
+
+/// Reloadable keys read by the status rotation
+const STATUS_KEYS: [&str; 8] = [
+  "params.status",
+  "params.status_time",
+  "params.status_mode",
+  "params.auto_status",
+  "params.dev_status",
+  "params.maintenance_status",
+  "params.debug_status",
+  "params.streaming_url"
+];
+
+/// Values of the config currently applied, flattened as `section.key`
+pub struct ConfigState {
+  /// the config is reloaded with the options given at startup
+  options: ConfigOptions,
+  values: BTreeMap<String, toml::Value>,
+  /// last modification of the config files known by this instance
+  modified: Option<SystemTime>
+}
+
+impl TypeMapKey for ConfigState {
+  type Value = Arc<Mutex<ConfigState>>;
+}
+
+impl ConfigState {
+  /// State of the config loaded at startup
+  pub fn new(options: ConfigOptions, loaded: &LoadedConfig) -> ConfigState {
+    let modified = modified_at(&options);
+    ConfigState { options, values: loaded.values.clone(), modified }
+  }
+}
+
+#[derive(Debug, Default)]
+pub struct ReloadReport {
+  pub applied: Vec<String>,
+  /// changed but only read at startup
+  pub restart: Vec<String>
+}
+
+impl ReloadReport {
+  pub fn is_empty(&self) -> bool {
+    self.applied.is_empty() && self.restart.is_empty()
+  }
+
+  /// Whether `key` or one of its sub-keys was applied
+  fn changed(&self, key: &str) -> bool {
+    self.applied.iter().any(|k| covers(key, k))
+  }
+}
+
+/// Validated config waiting to be applied
+pub struct PendingReload {
+  config: Config,
+  values: BTreeMap<String, toml::Value>,
+  pub report: ReloadReport
+}
+
+/// Read and validate the config file and list the changed keys, nothing is applied yet
+pub async fn prepare(data: &Arc<RwLock<TypeMap>>) -> Result<PendingReload, Vec<String>> {
+  let state_lock = {
+    let data = data.read().await;
+    data.get::<ConfigState>().expect("Expected ConfigState in TypeMap.").clone()
+  };
+  let state = state_lock.lock().await;
+
+  let LoadedConfig { config, values, .. } = init::load_config(&state.options)
+    .map_err(|problems| problems.iter().map(|p| p.to_string()).collect::<Vec<String>>())?;
+
+  let mut report = ReloadReport::default();
+  for key in changed_keys(&state.values, &values) {
+    if RELOADABLE_KEYS.iter().any(|reloadable| covers(reloadable, &key)) { report.applied.push(key) }
+    else { report.restart.push(key) }
+  }
+
+  Ok(PendingReload { config, values, report })
+}
+
+impl PendingReload {
+  /// Write the reloadable keys into the storage, the statuses and the languages.
+  /// Takes the storage write lock, must not be awaited by a command holding the storage
+  pub async fn apply(self, data: &Arc<RwLock<TypeMap>>) {
+    let (storage_lock, status_lock, state_lock) = {
+      let data = data.read().await;
+      (
+        data.get::<Storage>().expect("Expected Storage in TypeMap.").clone(),
+        data.get::<StatusManager>().expect("Expected StatusManager in TypeMap.").clone(),
+        data.get::<ConfigState>().expect("Expected ConfigState in TypeMap.").clone()
+      )
+    };
+
+    storage_lock.write().await.apply_config(&self.config);
+
+    if STATUS_KEYS.iter().any(|key| self.report.changed(key)) {
+      let mut manager = status_lock.write().await;
+      manager.apply_config(&self.config);
+      if let Err(err) = manager.save() {
+        utils::error("ConfigReload", "cannot save the reloaded statuses", err.as_str());
+      }
+    }
+
+    if self.report.changed("i18n.locales_dir") {
+      i18n::load(&self.config.i18n.locales_dir).await;
+    }
+
+    let mut state = state_lock.lock().await;
+    state.values = self.values;
+    state.modified = modified_at(&state.options);
+    drop(state);
+
+    log_report(&self.report);
+  }
+}
+
+/// Reload the config and apply it, used by the file watch and SIGHUP
+pub async fn reload(data: &Arc<RwLock<TypeMap>>, trigger: &str) {
+  match prepare(data).await {
+    Ok(pending) => {
+      if pending.report.is_empty() {
+        utils::info("ConfigReload", format!("Config reloaded ({trigger}), nothing changed").as_str());
+        mark_known(data).await;
+        return
+      }
+      utils::info("ConfigReload", format!("Reloading the config ({trigger})").as_str());
+      pending.apply(data).await;
+    }
+    Err(problems) => {
+      utils::error("ConfigReload", "the config is invalid, the current one is kept", problems.join(" | ").as_str());
+      mark_known(data).await;
+    }
+  }
+}
+
+/// Reload when a config file is modified or when SIGHUP is received
+pub async fn watch(data: Arc<RwLock<TypeMap>>) {
+  let mut hangup = Hangup::new();
+  loop {
+    tokio::select! {
+      _ = sleep(Duration::from_secs(5)) => {
+        let state_lock = {
+          let data = data.read().await;
+          data.get::<ConfigState>().expect("Expected ConfigState in TypeMap.").clone()
+        };
+        let (known, current) = {
+          let state = state_lock.lock().await;
+          (state.modified, modified_at(&state.options))
+        };
+        if current.is_none() || current == known { continue; }
+        reload(&data, "file modified").await;
+      }
+      _ = hangup.recv() => reload(&data, "SIGHUP").await
+    }
+  }
+}
+
+fn changed_keys(old: &BTreeMap<String, toml::Value>, new: &BTreeMap<String, toml::Value>) -> Vec<String> {
+  let mut keys: Vec<String> = new.iter().filter(|(key, value)| old.get(*key) != Some(value)).map(|(key, _)| key.clone()).collect();
+  keys.extend(old.keys().filter(|key| !new.contains_key(*key)).cloned());
+  keys.sort();
+  keys
+}
+
+/// `params.status` covers `params.status` and `params.dev_status.message` is covered by `params.dev_status`
+fn covers(parent: &str, key: &str) -> bool {
+  key == parent || key.strip_prefix(parent).map(|rest| rest.starts_with('.')).unwrap_or(false)
+}
+
+fn log_report(report: &ReloadReport) {
+  if !report.applied.is_empty() {
+    utils::success("ConfigReload", format!("Applied: {}", report.applied.join(", ")).as_str());
+  }
+  if !report.restart.is_empty() {
+    utils::warn("ConfigReload", format!("Changed but a restart is needed: {}", report.restart.join(", ")).as_str());
+  }
+}
+
+/// The file is not checked again until its next modification
+async fn mark_known(data: &Arc<RwLock<TypeMap>>) {
+  let state_lock = {
+    let data = data.read().await;
+    data.get::<ConfigState>().expect("Expected ConfigState in TypeMap.").clone()
+  };
+  let mut state = state_lock.lock().await;
+  state.modified = modified_at(&state.options);
+}
+
+/// Latest modification of the config file and its overlay
+fn modified_at(options: &ConfigOptions) -> Option<SystemTime> {
+  options.files().iter().filter_map(|path| fs::metadata(path).and_then(|m| m.modified()).ok()).max()
+}
+
+#[cfg(unix)]
+struct Hangup(Option<tokio::signal::unix::Signal>);
+
+#[cfg(unix)]
+impl Hangup {
+  fn new() -> Hangup {
+    match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+      Ok(signal) => Hangup(Some(signal)),
+      Err(err) => {
+        utils::warn_with_cause("ConfigReload", "cannot listen for SIGHUP, only the file is watched", err.to_string().as_str());
+        Hangup(None)
+      }
+    }
+  }
+
+  async fn recv(&mut self) {
+    match &mut self.0 {
+      Some(signal) => { signal.recv().await; },
+      None => std::future::pending::<()>().await
+    }
+  }
+}
+
+/// No SIGHUP outside of unix, only the file is watched
+#[cfg(not(unix))]
+struct Hangup;
+
+#[cfg(not(unix))]
+impl Hangup {
+  fn new() -> Hangup { Hangup }
+
+  async fn recv(&mut self) {
+    std::future::pending::<()>().await
+  }
+}
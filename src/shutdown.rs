@@ -0,0 +1,229 @@
+use std::{ sync::{ Arc, atomic::{ AtomicBool, AtomicUsize, Ordering } }, time::{ Duration, Instant } };
+use serenity::{ async_trait, client::bridge::gateway::ShardManager, prelude::{ Mutex, RwLock, TypeMap } };
+use tokio::{ signal, time::{ sleep, timeout } };
+
+use crate::{ utils, storage::Database };
+#[cfg(feature = "private")]
+use crate::libs::security::archive::Archive;
+
+/// Every hook ran successfully
+pub const EXIT_CLEAN: i32 = 0;
+/// The signals cannot be listened to
+pub const EXIT_SIGNAL_ERROR: i32 = 1;
+/// A hook failed or timed out, or the shutdown was forced: some state may be lost
+pub const EXIT_INCOMPLETE: i32 = 4;
+
+static SHUTTING_DOWN: AtomicBool = AtomicBool::new(false);
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+pub fn is_shutting_down() -> bool {
+  SHUTTING_DOWN.load(Ordering::SeqCst)
+}
+
+/// Held while a command runs, the shutdown waits until every guard is dropped
+pub struct InFlight;
+
+impl InFlight {
+  /// `None` once the shutdown started, no new command is accepted
+  pub fn start() -> Option<InFlight> {
+    if is_shutting_down() { return None }
+    IN_FLIGHT.fetch_add(1, Ordering::SeqCst);
+    Some(InFlight)
+  }
+}
+
+impl Drop for InFlight {
+  fn drop(&mut self) {
+    IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+  }
+}
+
+pub struct ShutdownContext {
+  pub data: Arc<RwLock<TypeMap>>,
+  pub shard_manager: Arc<Mutex<ShardManager>>,
+  /// signal which started the shutdown
+  pub reason: &'static str
+}
+
+/// Step of the shutdown, the hooks run one after the other in registration order
+#[async_trait]
+pub trait ShutdownHook: Send + Sync {
+  fn name(&self) -> &'static str;
+
+  /// the next hook runs once this delay is over, even if this one did not finish
+  fn timeout(&self) -> Duration {
+    Duration::from_secs(5)
+  }
+
+  async fn run(&self, shutdown: &ShutdownContext) -> Result<(), String>;
+}
+
+pub struct Coordinator {
+  hooks: Vec<Box<dyn ShutdownHook>>
+}
+
+impl Coordinator {
+  /// Coordinator with the built-in hooks: in-flight commands, goodbye log, shards, archive and database
+  pub fn new() -> Coordinator {
+    let mut coordinator = Coordinator { hooks: Vec::new() };
+    coordinator.register(InFlightCommands);
+    coordinator.register(Goodbye);
+    coordinator.register(Shards);
+    #[cfg(feature = "private")]
+    coordinator.register(FlushArchive);
+    coordinator.register(CloseDatabase);
+    coordinator
+  }
+
+  /// Add a hook, run after the ones already registered
+  pub fn register(&mut self, hook: impl ShutdownHook + 'static) {
+    self.hooks.push(Box::new(hook));
+  }
+
+  /// Wait for SIGINT or SIGTERM, run the hooks and exit the process.
+  /// A second signal during the shutdown exits right away
+  pub async fn listen(self, data: Arc<RwLock<TypeMap>>, shard_manager: Arc<Mutex<ShardManager>>) {
+    let reason = match wait_for_signal().await {
+      Ok(reason) => reason,
+      Err(err) => {
+        utils::error("Shutdown", "Unable to listen for shutdown signal", err.to_string().as_str());
+        std::process::exit(EXIT_SIGNAL_ERROR);
+      }
+    };
+    utils::info("MioEngine", format!("Exit Signal received ({reason})").as_str());
+    SHUTTING_DOWN.store(true, Ordering::SeqCst);
+
+    let shutdown = ShutdownContext { data, shard_manager, reason };
+    let code = tokio::select! {
+      code = self.run(&shutdown) => code,
+      _ = wait_for_signal() => {
+        utils::warn("Shutdown", "Second signal received, the shutdown is forced");
+        EXIT_INCOMPLETE
+      }
+    };
+
+    if code == EXIT_CLEAN { utils::success("MioEngine", format!("Exit code {code}").as_str()); }
+    else { utils::warn("MioEngine", format!("Exit code {code}, the shutdown was incomplete").as_str()); }
+    std::process::exit(code);
+  }
+
+  async fn run(&self, shutdown: &ShutdownContext) -> i32 {
+    let mut code = EXIT_CLEAN;
+    for hook in self.hooks.iter() {
+      let start = Instant::now();
+      match timeout(hook.timeout(), hook.run(shutdown)).await {
+        Ok(Ok(())) => utils::info("Shutdown", format!("{} done in {}ms", hook.name(), start.elapsed().as_millis()).as_str()),
+        Ok(Err(err)) => {
+          utils::error("Shutdown", format!("{} failed", hook.name()).as_str(), err.as_str());
+          code = EXIT_INCOMPLETE;
+        }
+        Err(_) => {
+          utils::error("Shutdown", format!("{} did not finish in time", hook.name()).as_str(), format!("timeout of {}s", hook.timeout().as_secs()).as_str());
+          code = EXIT_INCOMPLETE;
+        }
+      }
+    }
+    code
+  }
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() -> std::io::Result<&'static str> {
+  let mut terminate = signal::unix::signal(signal::unix::SignalKind::terminate())?;
+  tokio::select! {
+    result = signal::ctrl_c() => result.map(|_| "SIGINT"),
+    _ = terminate.recv() => Ok("SIGTERM")
+  }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() -> std::io::Result<&'static str> {
+  signal::ctrl_c().await.map(|_| "Ctrl-C")
+}
+
+// ==================================
+// built-in hooks
+
+/// Let the running commands finish, new ones are refused since the shutdown started
+struct InFlightCommands;
+
+#[async_trait]
+impl ShutdownHook for InFlightCommands {
+  fn name(&self) -> &'static str { "InFlightCommands" }
+
+  fn timeout(&self) -> Duration { Duration::from_secs(15) }
+
+  async fn run(&self, _shutdown: &ShutdownContext) -> Result<(), String> {
+    let running = IN_FLIGHT.load(Ordering::SeqCst);
+    if running > 0 {
+      utils::info("Shutdown", format!("Waiting for {running} running command.s").as_str());
+    }
+    while IN_FLIGHT.load(Ordering::SeqCst) > 0 {
+      sleep(Duration::from_millis(100)).await;
+    }
+    Ok(())
+  }
+}
+
+struct Goodbye;
+
+#[async_trait]
+impl ShutdownHook for Goodbye {
+  fn name(&self) -> &'static str { "Goodbye" }
+
+  async fn run(&self, shutdown: &ShutdownContext) -> Result<(), String> {
+    utils::info("MioEngine", format!("Goodbye! Shutting down after {}", shutdown.reason).as_str());
+    Ok(())
+  }
+}
+
+struct Shards;
+
+#[async_trait]
+impl ShutdownHook for Shards {
+  fn name(&self) -> &'static str { "Shards" }
+
+  fn timeout(&self) -> Duration { Duration::from_secs(10) }
+
+  async fn run(&self, shutdown: &ShutdownContext) -> Result<(), String> {
+    let mut shards = shutdown.shard_manager.lock().await;
+    utils::info("MioEngine", format!("Shutting down all shards... ({} shard.s)", shards.shards_instantiated().await.len()).as_str());
+    shards.shutdown_all().await;
+    utils::success("MioEngine", "All shards have been killed");
+    Ok(())
+  }
+}
+
+#[cfg(feature = "private")]
+struct FlushArchive;
+
+#[cfg(feature = "private")]
+#[async_trait]
+impl ShutdownHook for FlushArchive {
+  fn name(&self) -> &'static str { "FlushArchive" }
+
+  async fn run(&self, shutdown: &ShutdownContext) -> Result<(), String> {
+    let archive_lock = {
+      let data = shutdown.data.read().await;
+      data.get::<Archive>().ok_or("no archive in the TypeMap")?.clone()
+    };
+    let mut archive = archive_lock.write().await;
+    archive.save()
+  }
+}
+
+/// Take the connection out of the TypeMap so it can be closed properly
+struct CloseDatabase;
+
+#[async_trait]
+impl ShutdownHook for CloseDatabase {
+  fn name(&self) -> &'static str { "CloseDatabase" }
+
+  async fn run(&self, shutdown: &ShutdownContext) -> Result<(), String> {
+    let db = shutdown.data.write().await.remove::<Database>().ok_or("no database in the TypeMap")?;
+    match Arc::try_unwrap(db) {
+      Ok(conn) => sqlx::Connection::close(conn.into_inner()).await.map_err(|e| e.to_string()),
+      Err(_) => Err("the connection is still used, it is dropped without being closed".to_string())
+    }
+  }
+}
@@ -0,0 +1,814 @@
+use std::{ fs, sync::Arc, time::Duration };
+use chrono::{ Datelike, Local, NaiveDateTime, Timelike, Utc };
+use rand::{ rngs::StdRng, Rng, SeedableRng };
+use serde::{ Deserialize, Serialize };
+use serenity::{
+  client::bridge::gateway::{ ShardManager, ShardMessenger },
+  model::{ prelude::Activity, user::OnlineStatus },
+  prelude::{ TypeMapKey, RwLock, Mutex, TypeMap }
+};
+use tokio::{ sync::Notify, time::sleep };
+
+use crate::{ utils, init::{ self, Config }, storage::Storage, maintenance::Maintenance };
+
+pub const STATUS_FILE: &str = "./status.json";
+/// Discord limits the presence updates, a shorter interval is refused
+pub const MIN_STATUS_TIME: u64 = 20;
+
+#[derive(Clone, Copy)]
+pub enum ClientActivityType {
+  Playing = 0,
+  Streaming = 1,
+  Listening = 2,
+  Watching = 3,
+  Unknown = !0,
+}
+
+impl ClientActivityType {
+  /// Parse the `status_type` of the config, case-insensitive
+  pub fn from_str(value: &str) -> ClientActivityType {
+    match value.to_uppercase().as_str() {
+      "PLAYING" => ClientActivityType::Playing,
+      "STREAMING" => ClientActivityType::Streaming,
+      "LISTENING" => ClientActivityType::Listening,
+      "WATCHING" => ClientActivityType::Watching,
+      _ => ClientActivityType::Unknown
+    }
+  }
+
+  pub fn as_str(&self) -> &str {
+    match self {
+      ClientActivityType::Playing => "PLAYING",
+      ClientActivityType::Streaming => "STREAMING",
+      ClientActivityType::Listening => "LISTENING",
+      ClientActivityType::Watching => "WATCHING",
+      ClientActivityType::Unknown => "UNKNOWN"
+    }
+  }
+
+  pub fn is_unknown(&self) -> bool {
+    matches!(self, ClientActivityType::Unknown)
+  }
+}
+
+/// Order in which the statuses of the list are shown
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RotationMode {
+  Sequential,
+  Random,
+  /// random, the statuses with a higher `weight` are shown more often
+  Weighted
+}
+
+impl RotationMode {
+  pub fn from_str(value: &str) -> Option<RotationMode> {
+    match value.to_lowercase().as_str() {
+      "sequential" => Some(RotationMode::Sequential),
+      "random" => Some(RotationMode::Random),
+      "weighted" => Some(RotationMode::Weighted),
+      _ => None
+    }
+  }
+
+  pub fn as_str(&self) -> &str {
+    match self {
+      RotationMode::Sequential => "sequential",
+      RotationMode::Random => "random",
+      RotationMode::Weighted => "weighted"
+    }
+  }
+}
+
+pub fn online_status_from_str(value: &str) -> Option<OnlineStatus> {
+  match value.to_lowercase().as_str() {
+    "online" => Some(OnlineStatus::Online),
+    "idle" => Some(OnlineStatus::Idle),
+    "dnd" | "do_not_disturb" => Some(OnlineStatus::DoNotDisturb),
+    "invisible" => Some(OnlineStatus::Invisible),
+    _ => None
+  }
+}
+
+/// Period during which a status can be shown, both bounds are included and can wrap
+/// around the end of the year (`12-20` to `01-05`) or midnight (`22-6`)
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Schedule {
+  /// (month, day) of the first and last days
+  pub dates: Option<((u32, u32), (u32, u32))>,
+  /// first and last hours, local time
+  pub hours: Option<(u32, u32)>
+}
+
+impl Schedule {
+  /// `from` and `until` are `MM-DD` dates, `hours` is `HH-HH`
+  pub fn parse(from: Option<&str>, until: Option<&str>, hours: Option<&str>) -> Result<Option<Schedule>, String> {
+    let dates = match (from, until) {
+      (None, None) => None,
+      (Some(from), Some(until)) => Some((parse_pair(from, 12, 31)?, parse_pair(until, 12, 31)?)),
+      _ => return Err("`from` and `until` must be given together".to_string())
+    };
+    let hours = match hours {
+      Some(hours) => Some(parse_pair(hours, 23, 23)?),
+      None => None
+    };
+    if dates.is_none() && hours.is_none() { return Ok(None) }
+    Ok(Some(Schedule { dates, hours }))
+  }
+
+  pub fn is_active(&self, now: NaiveDateTime) -> bool {
+    let dates = self.dates.map(|(from, until)| in_range((now.month(), now.day()), from, until)).unwrap_or(true);
+    let hours = self.hours.map(|(from, until)| in_range(now.hour(), from, until)).unwrap_or(true);
+    dates && hours
+  }
+
+  /// First day of the period, `MM-DD`
+  pub fn start_date(&self) -> Option<String> {
+    self.dates.map(|((m, d), _)| format!("{m:02}-{d:02}"))
+  }
+
+  /// Last day of the period, `MM-DD`
+  pub fn end_date(&self) -> Option<String> {
+    self.dates.map(|(_, (m, d))| format!("{m:02}-{d:02}"))
+  }
+
+  fn hours_str(&self) -> Option<String> {
+    self.hours.map(|(from, until)| format!("{from}-{until}"))
+  }
+}
+
+fn parse_pair(value: &str, max_first: u32, max_second: u32) -> Result<(u32, u32), String> {
+  let invalid = || format!("invalid range `{value}`");
+  let (first, second) = value.split_once('-').ok_or_else(invalid)?;
+  let first = first.trim().parse::<u32>().map_err(|_| invalid())?;
+  let second = second.trim().parse::<u32>().map_err(|_| invalid())?;
+  // dates start at 1, hours at 0
+  let min = if max_first == 12 { 1 } else { 0 };
+  if first < min || first > max_first || second < min || second > max_second { return Err(invalid()) }
+  Ok((first, second))
+}
+
+fn in_range<T: PartialOrd>(value: T, from: T, until: T) -> bool {
+  if from <= until { value >= from && value <= until }
+  else { value >= from || value <= until }
+}
+
+#[derive(Clone)]
+pub struct Status {
+  pub message: String,
+  pub status_type: ClientActivityType,
+  pub online_status: OnlineStatus,
+  /// chance to be picked in [RotationMode::Weighted], 0 never shows the status
+  pub weight: u32,
+  pub schedule: Option<Schedule>
+}
+
+impl Status {
+  pub fn new(message: String, status_type: ClientActivityType) -> Status {
+    Status { message, status_type, online_status: OnlineStatus::Online, weight: 1, schedule: None }
+  }
+
+  pub fn from_config(status: &init::Status) -> Result<Status, String> {
+    let status_type = ClientActivityType::from_str(&status.status_type);
+    if status_type.is_unknown() { return Err(format!("unknown status type `{}`", status.status_type)) }
+    let online_status = match &status.online_status {
+      Some(value) => online_status_from_str(value).ok_or(format!("unknown online status `{value}`"))?,
+      None => OnlineStatus::Online
+    };
+    let schedule = Schedule::parse(status.from.as_deref(), status.until.as_deref(), status.hours.as_deref())?;
+    Ok(Status { message: status.message.clone(), status_type, online_status, weight: status.weight, schedule })
+  }
+
+  pub fn to_config(&self) -> init::Status {
+    init::Status {
+      status_type: self.status_type.as_str().to_string(),
+      message: self.message.clone(),
+      weight: self.weight,
+      online_status: if self.online_status == OnlineStatus::Online { None } else { Some(self.online_status.name().to_string()) },
+      from: self.schedule.and_then(|s| s.start_date()),
+      until: self.schedule.and_then(|s| s.end_date()),
+      hours: self.schedule.and_then(|s| s.hours_str())
+    }
+  }
+
+  /// Activity sent to the gateway, `None` for an unknown type
+  pub fn activity(&self, message: String, streaming_url: &str) -> Option<Activity> {
+    match self.status_type {
+      ClientActivityType::Playing => Some(Activity::playing(message)),
+      ClientActivityType::Watching => Some(Activity::watching(message)),
+      ClientActivityType::Listening => Some(Activity::listening(message)),
+      ClientActivityType::Streaming => Some(Activity::streaming(message, streaming_url)),
+      ClientActivityType::Unknown => None
+    }
+  }
+}
+
+/// Statuses edited at runtime, persisted in [STATUS_FILE].
+///
+/// The latest change wins: the file is used at startup as long as the status keys of the config
+/// are the ones it was based on. Once they are edited, by a reload or while the bot was stopped,
+/// the config replaces the runtime edits.
+#[derive(Serialize, Deserialize)]
+struct PersistedStatuses {
+  list: Vec<init::Status>,
+  status_time: u64,
+  #[serde(default)]
+  mode: Option<String>,
+  /// status keys of the config when the file was written, see [config_base]
+  #[serde(default)]
+  base: Option<String>
+}
+
+pub struct StatusManager {
+  pub list: Vec<Status>,
+  /// rotate the statuses of [StatusManager::list], from `auto_status`
+  pub continue_status: bool,
+  pub mode: RotationMode,
+  pub dev_status: Status,
+  pub maintenance_status: Status,
+  pub debug_mode_status: Status,
+  pub streaming_url: String,
+  pub status_time: u64,
+  /// position of the status currently shown in the list
+  pub index: usize,
+  force_rotation: bool,
+  /// status keys of the config the list is based on, written with the runtime edits
+  base: String,
+  /// wakes the status loop up when the interval changes or a rotation is asked
+  wake: Arc<Notify>,
+  rng: StdRng
+}
+
+impl TypeMapKey for StatusManager {
+  type Value = Arc<RwLock<StatusManager>>;
+}
+
+impl StatusManager {
+  pub fn new(config: &Config) -> StatusManager {
+    let mode = RotationMode::from_str(&config.params.status_mode).unwrap_or_else(|| {
+      utils::warn("StatusLoop", format!("Unknown status mode `{}`, sequential is used", config.params.status_mode).as_str());
+      RotationMode::Sequential
+    });
+    let mut manager = StatusManager {
+      list: status_list(&config.params.status),
+      continue_status: config.params.auto_status,
+      mode,
+      dev_status: mode_status(&config.params.dev_status),
+      maintenance_status: mode_status(&config.params.maintenance_status),
+      debug_mode_status: mode_status(&config.params.debug_status),
+      streaming_url: config.params.streaming_url.clone(),
+      status_time: (config.params.status_time.max(0) as u64).max(MIN_STATUS_TIME),
+      index: 0,
+      force_rotation: false,
+      base: config_base(config),
+      wake: Arc::new(Notify::new()),
+      rng: StdRng::from_entropy()
+    };
+
+    // a missing file only means nothing was edited at runtime
+    match fs::read_to_string(STATUS_FILE) {
+      Ok(content) => match serde_json::from_str::<PersistedStatuses>(&content) {
+        Ok(persisted) => {
+          if manager.restore(persisted) {
+            utils::info("StatusLoop", format!("{} statuses loaded from {STATUS_FILE}", manager.list.len()).as_str());
+          } else {
+            utils::info("StatusLoop", format!("The statuses of the config changed since {STATUS_FILE} was written, the config is used").as_str());
+          }
+        },
+        Err(err) => utils::error("StatusLoop", format!("cannot parse {STATUS_FILE}, the statuses of the config are used").as_str(), err.to_string().as_str())
+      },
+      Err(err) if err.kind() == std::io::ErrorKind::NotFound => {},
+      Err(err) => utils::error("StatusLoop", format!("cannot read {STATUS_FILE}, the statuses of the config are used").as_str(), err.to_string().as_str())
+    }
+
+    manager
+  }
+
+  /// Use the runtime edits of [STATUS_FILE], unless the config they were based on changed since
+  fn restore(&mut self, persisted: PersistedStatuses) -> bool {
+    // files written before the base was recorded are kept
+    if persisted.base.map(|base| base != self.base).unwrap_or(false) { return false }
+    self.list = status_list(&persisted.list);
+    self.status_time = persisted.status_time.max(MIN_STATUS_TIME);
+    if let Some(mode) = persisted.mode.as_deref().and_then(RotationMode::from_str) { self.mode = mode }
+    true
+  }
+
+  pub fn save(&self) -> Result<(), String> {
+    let persisted = PersistedStatuses {
+      list: self.list.iter().map(|s| s.to_config()).collect(),
+      status_time: self.status_time,
+      mode: Some(self.mode.as_str().to_string()),
+      base: Some(self.base.clone())
+    };
+    let content = serde_json::to_string_pretty(&persisted).map_err(|e| e.to_string())?;
+    fs::write(STATUS_FILE, content).map_err(|e| e.to_string())
+  }
+
+  /// Replace the rotation by the one of a reloaded config, the next status is shown right away.
+  /// Only called when the status keys changed, they win over the runtime edits then
+  pub fn apply_config(&mut self, config: &Config) {
+    self.list = status_list(&config.params.status);
+    self.continue_status = config.params.auto_status;
+    if let Some(mode) = RotationMode::from_str(&config.params.status_mode) { self.mode = mode }
+    self.dev_status = mode_status(&config.params.dev_status);
+    self.maintenance_status = mode_status(&config.params.maintenance_status);
+    self.debug_mode_status = mode_status(&config.params.debug_status);
+    self.streaming_url = config.params.streaming_url.clone();
+    self.status_time = (config.params.status_time.max(0) as u64).max(MIN_STATUS_TIME);
+    self.base = config_base(config);
+    self.index = 0;
+    self.rotate_now();
+  }
+
+  pub fn add(&mut self, status: Status) {
+    self.list.push(status);
+  }
+
+  pub fn remove(&mut self, position: usize) -> Option<Status> {
+    if position >= self.list.len() { return None }
+    if position < self.index { self.index -= 1 }
+    Some(self.list.remove(position))
+  }
+
+  /// Move the status at `from` to `to`, both must be in the list
+  pub fn reorder(&mut self, from: usize, to: usize) -> bool {
+    if from >= self.list.len() || to >= self.list.len() { return false }
+    let status = self.list.remove(from);
+    self.list.insert(to, status);
+    true
+  }
+
+  /// Change the rotation interval, taken into account by the loop right away
+  pub fn set_interval(&mut self, seconds: u64) {
+    self.status_time = seconds.max(MIN_STATUS_TIME);
+    self.wake.notify_one();
+  }
+
+  /// Show the next status without waiting for the end of the interval
+  pub fn rotate_now(&mut self) {
+    self.force_rotation = true;
+    self.wake.notify_one();
+  }
+
+  /// Status to show now, the mode statuses have priority over the rotation.
+  /// `None` keeps the current presence: rotation disabled or nothing to show at `now`
+  fn next(&mut self, snapshot: &Snapshot, now: NaiveDateTime) -> Option<Status> {
+    if snapshot.dev { Some(self.dev_status.clone()) }
+    else if snapshot.debug { Some(self.debug_mode_status.clone()) }
+    else if snapshot.in_maintenance { Some(self.maintenance_status.clone()) }
+    else if !self.continue_status { None }
+    else {
+      let candidates = self.candidates(now);
+      let position = self.pick(&candidates)?;
+      self.index = position;
+      self.list.get(position).cloned()
+    }
+  }
+
+  /// Positions of the statuses which can be shown at `now`: the scheduled statuses replace
+  /// the others while they are active
+  fn candidates(&self, now: NaiveDateTime) -> Vec<usize> {
+    let scheduled: Vec<usize> = (0..self.list.len())
+      .filter(|i| self.list[*i].schedule.map(|s| s.is_active(now)).unwrap_or(false))
+      .collect();
+    if !scheduled.is_empty() { return scheduled }
+    (0..self.list.len()).filter(|i| self.list[*i].schedule.is_none()).collect()
+  }
+
+  fn pick(&mut self, candidates: &Vec<usize>) -> Option<usize> {
+    if candidates.is_empty() { return None }
+    match self.mode {
+      RotationMode::Sequential => {
+        Some(*candidates.iter().find(|i| **i > self.index).unwrap_or(&candidates[0]))
+      }
+      RotationMode::Random => {
+        // avoid showing the same status twice in a row
+        let others: Vec<usize> = candidates.iter().copied().filter(|i| *i != self.index).collect();
+        let pool = if others.is_empty() { candidates.clone() } else { others };
+        Some(pool[self.rng.gen_range(0..pool.len())])
+      }
+      RotationMode::Weighted => {
+        let total: u64 = candidates.iter().map(|i| self.list[*i].weight as u64).sum();
+        if total == 0 { return None }
+        let mut roll = self.rng.gen_range(0..total);
+        for i in candidates {
+          let weight = self.list[*i].weight as u64;
+          if roll < weight { return Some(*i) }
+          roll -= weight;
+        }
+        None
+      }
+    }
+  }
+}
+
+/// Keys of the config that [STATUS_FILE] overrides, compared to know which one is the latest
+fn config_base(config: &Config) -> String {
+  let params = &config.params;
+  serde_json::to_string(&(&params.status, params.status_time, &params.status_mode)).unwrap_or_default()
+}
+
+/// Statuses of the config, the invalid ones are ignored
+fn status_list(statuses: &[init::Status]) -> Vec<Status> {
+  statuses.iter()
+    .filter_map(|status| match Status::from_config(status) {
+      Ok(parsed) => Some(parsed),
+      Err(err) => {
+        utils::warn_with_cause("StatusLoop", format!("The status \"{}\" is ignored", status.message).as_str(), err.as_str());
+        None
+      }
+    })
+    .collect()
+}
+
+/// Dev, debug and maintenance statuses, an invalid one is shown as watching
+fn mode_status(status: &init::Status) -> Status {
+  Status::from_config(status).unwrap_or_else(|err| {
+    utils::warn_with_cause("StatusLoop", format!("The status \"{}\" is invalid, shown as WATCHING", status.message).as_str(), err.as_str());
+    Status::new(status.message.clone(), ClientActivityType::Watching)
+  })
+}
+
+// ==================================
+// placeholders
+
+/// Copy of the state used by a rotation, taken so no lock is kept while talking to the shards
+#[derive(Clone, Default)]
+pub struct Snapshot {
+  pub dev: bool,
+  pub debug: bool,
+  pub in_maintenance: bool,
+  pub guilds: usize,
+  pub users: u64,
+  pub uptime: Duration,
+  pub version: String,
+  pub prefix: String
+}
+
+impl Snapshot {
+  pub fn capture(storage: &Storage, in_maintenance: bool) -> Snapshot {
+    Snapshot {
+      dev: storage.dev,
+      debug: storage.debug,
+      in_maintenance,
+      guilds: storage.client.guilds.len(),
+      users: storage.client.user_count(),
+      uptime: (Utc::now() - storage.process_start).to_std().unwrap_or_default(),
+      version: storage.client.version.clone(),
+      prefix: storage.client.prefix.clone()
+    }
+  }
+}
+
+/// Values substituted in the status messages, built for every shard at each rotation
+pub struct Placeholders {
+  pub guilds: usize,
+  pub users: u64,
+  pub shard: u64,
+  pub shards: usize,
+  pub uptime: Duration,
+  pub version: String,
+  pub prefix: String,
+  /// latency of the shard, unknown until its first heartbeat
+  pub ping: Option<Duration>
+}
+
+impl Placeholders {
+  pub fn new(snapshot: &Snapshot, shard: u64, shards: usize, ping: Option<Duration>) -> Placeholders {
+    Placeholders {
+      guilds: snapshot.guilds,
+      users: snapshot.users,
+      shard,
+      shards,
+      uptime: snapshot.uptime,
+      version: snapshot.version.clone(),
+      prefix: snapshot.prefix.clone(),
+      ping
+    }
+  }
+}
+
+/// Replace the `{name}` placeholders of `template`, unknown ones are kept as they are
+pub fn render(template: &str, values: &Placeholders) -> String {
+  if !template.contains('{') { return template.to_string() }
+
+  let ping = match values.ping {
+    Some(ping) => format!("{}ms", ping.as_millis()),
+    None => "?".to_string()
+  };
+
+  template
+    .replace("{guilds}", values.guilds.to_string().as_str())
+    .replace("{users}", values.users.to_string().as_str())
+    .replace("{shard}", values.shard.to_string().as_str())
+    .replace("{shards}", values.shards.to_string().as_str())
+    .replace("{uptime}", format_uptime(values.uptime).as_str())
+    .replace("{version}", values.version.as_str())
+    .replace("{prefix}", values.prefix.as_str())
+    .replace("{ping}", ping.as_str())
+}
+
+/// Short uptime for a status, `2j 4h`, `4h 12m` or `12m`
+pub fn format_uptime(uptime: Duration) -> String {
+  let secs = uptime.as_secs();
+  let (days, hours, minutes) = (secs / 86400, (secs % 86400) / 3600, (secs % 3600) / 60);
+  if days > 0 { format!("{days}j {hours}h") }
+  else if hours > 0 { format!("{hours}h {minutes}m") }
+  else { format!("{minutes}m") }
+}
+
+// ==================================
+// loop
+
+/// Rotate the statuses of every shard, the interval is read again after each rotation.
+///
+/// Each lock is taken alone and released before the next one: the state is copied in a
+/// [Snapshot], the status picked, then the shards are listed and updated without any lock.
+pub async fn run(data: Arc<RwLock<TypeMap>>, shard_manager: Arc<Mutex<ShardManager>>) {
+  let (storage_lock, maintenance_lock, status_lock) = {
+    let data = data.read().await;
+    (
+      data.get::<Storage>().expect("Expected Storage in TypeMap").clone(),
+      data.get::<Maintenance>().expect("Expected Maintenance in TypeMap").clone(),
+      data.get::<StatusManager>().expect("Expected StatusManager in TypeMap").clone()
+    )
+  };
+
+  loop {
+    let (interval, wake) = {
+      let status = status_lock.read().await;
+      (Duration::from_secs(status.status_time), status.wake.clone())
+    };
+    tokio::select! {
+      _ = sleep(interval) => {},
+      _ = wake.notified() => {
+        // a new interval only restarts the wait
+        let forced = std::mem::take(&mut status_lock.write().await.force_rotation);
+        if !forced { continue; }
+      }
+    }
+
+    let in_maintenance = maintenance_lock.read().await.enabled;
+    let snapshot = Snapshot::capture(&*storage_lock.read().await, in_maintenance);
+
+    let (new_state, streaming_url) = {
+      let mut status = status_lock.write().await;
+      match status.next(&snapshot, Local::now().naive_local()) {
+        Some(state) => (state, status.streaming_url.clone()),
+        None => continue
+      }
+    };
+
+    let shards: Vec<(u64, ShardMessenger, Option<Duration>)> = {
+      let manager = shard_manager.lock().await;
+      let runners = manager.runners.lock().await;
+      runners.iter().map(|(id, runner)| (id.0, runner.runner_tx.clone(), runner.latency)).collect()
+    };
+
+    for (id, messenger, latency) in shards.iter() {
+      // for every shards, with its own id and latency
+      let message = render(&new_state.message, &Placeholders::new(&snapshot, *id, shards.len(), *latency));
+      if let Some(activity) = new_state.activity(message, &streaming_url) {
+        messenger.set_presence(Some(activity), new_state.online_status);
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use chrono::NaiveDate;
+
+  fn status(message: &str) -> Status {
+    Status::new(message.to_string(), ClientActivityType::Watching)
+  }
+
+  fn scheduled(message: &str, from: Option<&str>, until: Option<&str>, hours: Option<&str>) -> Status {
+    let mut status = status(message);
+    status.schedule = Schedule::parse(from, until, hours).unwrap();
+    status
+  }
+
+  fn manager(list: Vec<Status>, mode: RotationMode) -> StatusManager {
+    StatusManager {
+      list,
+      continue_status: true,
+      mode,
+      dev_status: status("dev"),
+      maintenance_status: status("maintenance"),
+      debug_mode_status: status("debug"),
+      streaming_url: String::new(),
+      status_time: 60,
+      index: 0,
+      force_rotation: false,
+      base: String::new(),
+      wake: Arc::new(Notify::new()),
+      rng: StdRng::seed_from_u64(42)
+    }
+  }
+
+  fn at(month: u32, day: u32, hour: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2023, month, day).unwrap().and_hms_opt(hour, 0, 0).unwrap()
+  }
+
+  fn next_message(manager: &mut StatusManager, now: NaiveDateTime) -> Option<String> {
+    manager.next(&Snapshot::default(), now).map(|s| s.message)
+  }
+
+  #[test]
+  fn empty_list_keeps_the_presence() {
+    for mode in [RotationMode::Sequential, RotationMode::Random, RotationMode::Weighted] {
+      let mut manager = manager(vec![], mode);
+      assert_eq!(next_message(&mut manager, at(6, 1, 12)), None);
+    }
+  }
+
+  #[test]
+  fn sequential_cycles_through_the_list() {
+    let mut manager = manager(vec![status("a"), status("b"), status("c")], RotationMode::Sequential);
+    let shown: Vec<String> = (0..4).filter_map(|_| next_message(&mut manager, at(6, 1, 12))).collect();
+    assert_eq!(shown, vec!["b", "c", "a", "b"]);
+  }
+
+  #[test]
+  fn mode_statuses_have_priority() {
+    let mut manager = manager(vec![status("a")], RotationMode::Sequential);
+    let snapshot = Snapshot { in_maintenance: true, ..Default::default() };
+    assert_eq!(manager.next(&snapshot, at(6, 1, 12)).map(|s| s.message), Some("maintenance".to_string()));
+
+    let snapshot = Snapshot { dev: true, in_maintenance: true, ..Default::default() };
+    assert_eq!(manager.next(&snapshot, at(6, 1, 12)).map(|s| s.message), Some("dev".to_string()));
+  }
+
+  #[test]
+  fn disabled_rotation_shows_nothing() {
+    let mut manager = manager(vec![status("a"), status("b")], RotationMode::Sequential);
+    manager.continue_status = false;
+    assert_eq!(next_message(&mut manager, at(6, 1, 12)), None);
+  }
+
+  #[test]
+  fn random_never_repeats_a_status() {
+    let mut manager = manager(vec![status("a"), status("b"), status("c")], RotationMode::Random);
+    let mut previous = next_message(&mut manager, at(6, 1, 12));
+    for _ in 0..100 {
+      let current = next_message(&mut manager, at(6, 1, 12));
+      assert!(current.is_some());
+      assert_ne!(current, previous);
+      previous = current;
+    }
+  }
+
+  #[test]
+  fn weighted_follows_the_weights() {
+    let mut heavy = status("heavy");
+    heavy.weight = 3;
+    let mut never = status("never");
+    never.weight = 0;
+    let mut manager = manager(vec![heavy, status("light"), never], RotationMode::Weighted);
+
+    let draws = 4000;
+    let mut heavy_count = 0;
+    for _ in 0..draws {
+      match next_message(&mut manager, at(6, 1, 12)).as_deref() {
+        Some("heavy") => heavy_count += 1,
+        Some("light") => {}
+        other => panic!("unexpected status {other:?}")
+      }
+    }
+    let ratio = heavy_count as f64 / draws as f64;
+    assert!(ratio > 0.65 && ratio < 0.85, "ratio {ratio}");
+  }
+
+  #[test]
+  fn weighted_without_weight_shows_nothing() {
+    let mut zero = status("zero");
+    zero.weight = 0;
+    let mut manager = manager(vec![zero], RotationMode::Weighted);
+    assert_eq!(next_message(&mut manager, at(6, 1, 12)), None);
+  }
+
+  #[test]
+  fn active_schedule_replaces_the_others() {
+    let list = vec![status("a"), scheduled("christmas", Some("12-20"), Some("01-02"), None), status("b")];
+    let mut manager = manager(list, RotationMode::Sequential);
+
+    assert_eq!(next_message(&mut manager, at(12, 25, 12)).as_deref(), Some("christmas"));
+    assert_eq!(next_message(&mut manager, at(1, 1, 12)).as_deref(), Some("christmas"));
+    for _ in 0..4 {
+      assert_ne!(next_message(&mut manager, at(6, 1, 12)).as_deref(), Some("christmas"));
+    }
+  }
+
+  #[test]
+  fn schedule_wraps_around_midnight() {
+    let schedule = Schedule::parse(None, None, Some("22-6")).unwrap().unwrap();
+    assert!(schedule.is_active(at(6, 1, 23)));
+    assert!(schedule.is_active(at(6, 1, 3)));
+    assert!(!schedule.is_active(at(6, 1, 12)));
+  }
+
+  #[test]
+  fn invalid_schedules_are_refused() {
+    assert!(Schedule::parse(Some("12-20"), None, None).is_err());
+    assert!(Schedule::parse(Some("13-01"), Some("12-31"), None).is_err());
+    assert!(Schedule::parse(None, None, Some("8-24")).is_err());
+    assert!(Schedule::parse(None, None, Some("huit")).is_err());
+    assert_eq!(Schedule::parse(None, None, None), Ok(None));
+  }
+
+  #[test]
+  fn remove_and_reorder_keep_the_index_valid() {
+    let mut manager = manager(vec![status("a"), status("b"), status("c")], RotationMode::Sequential);
+    manager.index = 2;
+    assert_eq!(manager.remove(0).map(|s| s.message).as_deref(), Some("a"));
+    assert_eq!(manager.index, 1);
+    assert!(manager.remove(5).is_none());
+
+    assert!(!manager.reorder(0, 2));
+    assert!(manager.reorder(1, 0));
+    assert_eq!(manager.list.iter().map(|s| s.message.as_str()).collect::<Vec<_>>(), vec!["c", "b"]);
+
+    manager.remove(1);
+    manager.remove(0);
+    assert_eq!(next_message(&mut manager, at(6, 1, 12)), None);
+  }
+
+  #[test]
+  fn runtime_edits_win_until_the_config_changes() {
+    let persisted = |base: Option<&str>| PersistedStatuses {
+      list: vec![status("edited").to_config()],
+      status_time: 5,
+      mode: Some("random".to_string()),
+      base: base.map(|b| b.to_string())
+    };
+    let messages = |m: &StatusManager| m.list.iter().map(|s| s.message.clone()).collect::<Vec<String>>();
+
+    let mut current = manager(vec![status("config")], RotationMode::Sequential);
+    current.base = "current".to_string();
+    assert!(!current.restore(persisted(Some("previous"))));
+    assert_eq!(messages(&current), vec!["config"]);
+    assert_eq!(current.mode, RotationMode::Sequential);
+
+    assert!(current.restore(persisted(Some("current"))));
+    assert_eq!(messages(&current), vec!["edited"]);
+    assert_eq!(current.mode, RotationMode::Random);
+    assert_eq!(current.status_time, MIN_STATUS_TIME);
+
+    let mut legacy = manager(vec![status("config")], RotationMode::Sequential);
+    assert!(legacy.restore(persisted(None)));
+    assert_eq!(messages(&legacy), vec!["edited"]);
+  }
+
+  #[test]
+  fn config_round_trip() {
+    let config = init::Status {
+      status_type: "listening".to_string(),
+      message: "la nuit".to_string(),
+      weight: 2,
+      online_status: Some("idle".to_string()),
+      from: None,
+      until: None,
+      hours: Some("1-6".to_string())
+    };
+    let parsed = Status::from_config(&config).unwrap();
+    assert_eq!(parsed.status_type.as_str(), "LISTENING");
+    assert_eq!(parsed.online_status, OnlineStatus::Idle);
+
+    let back = parsed.to_config();
+    assert_eq!(back.status_type, "LISTENING");
+    assert_eq!(back.hours.as_deref(), Some("1-6"));
+    assert_eq!(back.weight, 2);
+
+    let unknown = init::Status { status_type: "DANCING".to_string(), ..config };
+    assert!(Status::from_config(&unknown).is_err());
+  }
+
+  #[test]
+  fn placeholders_are_rendered() {
+    let snapshot = Snapshot {
+      guilds: 12,
+      users: 3400,
+      uptime: Duration::from_secs(90061),
+      version: "1.0.0".to_string(),
+      prefix: "&".to_string(),
+      ..Default::default()
+    };
+    let values = Placeholders::new(&snapshot, 1, 2, Some(Duration::from_millis(42)));
+    assert_eq!(
+      render("{guilds} serveurs, {users} membres | shard {shard}/{shards} {ping} | {uptime} | v{version} {prefix}help {unknown}", &values),
+      "12 serveurs, 3400 membres | shard 1/2 42ms | 1j 1h | v1.0.0 &help {unknown}"
+    );
+
+    let values = Placeholders::new(&snapshot, 0, 1, None);
+    assert_eq!(render("{ping}", &values), "?");
+  }
+
+  #[test]
+  fn uptime_is_short() {
+    assert_eq!(format_uptime(Duration::from_secs(59)), "0m");
+    assert_eq!(format_uptime(Duration::from_secs(3660)), "1h 1m");
+    assert_eq!(format_uptime(Duration::from_secs(2 * 86400 + 4 * 3600)), "2j 4h");
+  }
+}
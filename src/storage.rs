@@ -1,46 +1,38 @@
-#![allow(dead_code)]
-
 use std::{sync::Arc, collections::HashMap, time::Duration};
 use chrono::{Utc, DateTime};
-use serenity::prelude::{TypeMapKey, RwLock};
-use crate::init::Config;
-
-#[derive(Clone, Copy)]
-pub enum ClientActivityType {
-  Playing = 0,
-  Streaming = 1,
-  Listening = 2,
-  Watching = 3,
-  Unknown = !0,
-}
+use serenity::{prelude::{TypeMapKey, RwLock, Mutex}, model::id::{UserId, GuildId}};
+use sqlx::MySqlConnection;
+use crate::{ init::Config, latency::LatencyHistory };
 
-impl ClientActivityType {
-  pub fn is_unknown(&self) -> bool {
-    match self {
-      ClientActivityType::Unknown => true,
-      _ => false
-    }
-  }
+pub struct ClientData {
+  /// set once the client is ready
+  pub id: Option<UserId>,
+  /// users allowed to run owner-only commands, from the config and the application owners
+  pub owners: Vec<UserId>,
+  pub prefix: String,
+  pub case_insensitive: bool,
+  /// guilds where the guild-scoped application commands are registered
+  pub slash_guilds: Vec<GuildId>,
+  pub version: String,
+  pub build_type: String,
+  /// every guild the client is in, kept up to date by the guild events
+  pub guilds: HashMap<GuildId, GuildCounts>
 }
 
-#[derive(Clone)]
-pub struct Status {
-  pub message: String,
-  pub status_type: ClientActivityType
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GuildCounts {
+  pub members: u64,
+  pub channels: u64
 }
 
-pub struct StatusManager {
-  pub list: Vec<Status>,
-  pub continue_status: bool,
-  pub dev_status: Status,
-  pub maintenance_status: Status,
-  pub debug_mode_status: Status,
-  pub streaming_url: String,
-  pub status_time: u64
-}
+impl ClientData {
+  pub fn user_count(&self) -> u64 {
+    self.guilds.values().map(|g| g.members).sum()
+  }
 
-pub struct ClientData {
-  pub prefix: String
+  pub fn channel_count(&self) -> u64 {
+    self.guilds.values().map(|g| g.channels).sum()
+  }
 }
 
 pub enum HandlerStatus {
@@ -50,34 +42,27 @@ pub enum HandlerStatus {
 }
 
 impl HandlerStatus {
-    pub fn as_i32(&self) -> i32 {
-        match self {
-            HandlerStatus::InDev => 0,
-            HandlerStatus::DebugMode => 1,
-            HandlerStatus::ProdMode => 2
-        }
-    }
-
     pub fn is_dev(&self) -> bool {
-      match self {
-        HandlerStatus::InDev | HandlerStatus::DebugMode => true,
-        _ => false
-      }
+      matches!(self, HandlerStatus::InDev | HandlerStatus::DebugMode)
     }
 }
 
 
 #[derive(Debug, Clone)]
 pub struct Latency {
+  /// last latency reported by the shard
   pub ping: Duration,
-  pub warned: bool
+  /// the shard is degraded, set and cleared by the thresholds of the monitor
+  pub warned: bool,
+  pub history: LatencyHistory,
+  /// restarts by the watchdog since the start of the process
+  pub restarts: u32,
+  pub disconnects: u32
 }
 
 pub struct Storage {
-  pub maintenance: bool,
   pub dev: bool,
   pub debug: bool,
-  pub status: StatusManager,
   pub client: ClientData,
   pub handler_state: HandlerStatus,
   pub latency: HashMap<u64, Latency>,
@@ -88,28 +73,39 @@ impl TypeMapKey for Storage {
   type Value = Arc<RwLock<Storage>>;
 }
 
+/// MySQL connection shared with the commands
+pub struct Database;
+
+impl TypeMapKey for Database {
+  type Value = Arc<Mutex<MySqlConnection>>;
+}
+
 impl Storage {
   pub fn new(config: &Config) -> Storage {
     Storage {
-      maintenance: false,
       dev: config.client.dev,
       debug: false,
-      client: ClientData { prefix: config.params.prefix.clone() },
-      status: StatusManager {
-        list: vec![
-          Status { message: "a".to_string(), status_type: ClientActivityType::Listening },
-          Status { message: "b".to_string(), status_type: ClientActivityType::Listening }
-        ],
-        continue_status: true,
-        dev_status: Status { message: "⚙️ Mode développeur".to_string(), status_type: ClientActivityType::Watching },
-        maintenance_status: Status { message: "🚧 Mode maintenance".to_string(), status_type: ClientActivityType::Watching },
-        debug_mode_status: Status { message: "🔧 Mode debug".to_string(), status_type: ClientActivityType::Watching },
-        streaming_url: "https://www.twitch.tv/sedorriku_".to_string(),
-        status_time: config.params.status_time.clone() as u64
+      client: ClientData {
+        id: None,
+        owners: config.params.owners.iter().map(|id| UserId(*id)).collect(),
+        prefix: config.params.prefix.clone(),
+        case_insensitive: config.params.case_insensitive_commands,
+        slash_guilds: config.params.slash_guilds.iter().map(|id| GuildId(*id)).collect(),
+        version: config.client.version.clone(),
+        build_type: config.client.build_type.clone(),
+        guilds: HashMap::new()
       },
       handler_state: if config.client.dev { HandlerStatus::InDev } else if false { HandlerStatus::DebugMode } else { HandlerStatus::ProdMode },
       latency: HashMap::new(),
       process_start: Utc::now()
     }
   }
+
+  /// Keys of a reloaded config which can change at runtime, see [crate::reload]
+  pub fn apply_config(&mut self, config: &Config) {
+    self.dev = config.client.dev;
+    self.handler_state = if config.client.dev { HandlerStatus::InDev } else if self.debug { HandlerStatus::DebugMode } else { HandlerStatus::ProdMode };
+    self.client.prefix = config.params.prefix.clone();
+    self.client.case_insensitive = config.params.case_insensitive_commands;
+  }
 }
\ No newline at end of file
@@ -1,80 +1,59 @@
 use chrono::{DateTime, Local};
+use crate::logging::{ self, Level, Record };
+
+// thin wrappers around the logging backend, the type is used as the module of the filter
+
+fn log(level: Level, module: &str, message: &str, detail: Option<(&'static str, &str)>, color: i32) {
+  logging::log(Record { level, module, message, detail, color, security: false });
+}
 
 pub fn error(error_type: &str, message: &str, cause: &str) {
-  println!(
-    "\x1b[2m({d})\x1b[0m [\x1b[31m{error_type}\x1b[0m]: \x1b[31m{message}\n                         Cause: {cause}\x1b[0m",
-    d = format_date(Local::now(), "%d/%m/%Y %H:%M:%S")
-  )
+  log(Level::Error, error_type, message, Some(("Cause", cause)), 31)
 }
 
 pub fn error_without_cause(error_type: &str, message: &str) {
-  println!(
-    "\x1b[2m({d})\x1b[0m [\x1b[31m{error_type}\x1b[0m]: \x1b[31m{message}\x1b[0m",
-    d = format_date(Local::now(), "%d/%m/%Y %H:%M:%S")
-  )
+  log(Level::Error, error_type, message, None, 31)
 }
 
 pub fn error_help(error_type: &str, message: &str, help: &str) {
-  println!(
-    "\x1b[2m({d})\x1b[0m [\x1b[31m{error_type}\x1b[0m]: \x1b[31m{message}\n                         Help: {help}\x1b[0m",
-    d = format_date(Local::now(), "%d/%m/%Y %H:%M:%S")
-  )
+  log(Level::Error, error_type, message, Some(("Help", help)), 31)
 }
 
 pub fn warn(warn_type: &str, message: &str) {
-  println!(
-    "\x1b[2m({d})\x1b[0m [\x1b[33m{warn_type}\x1b[0m]: \x1b[33m{message}\x1b[0m",
-    d = format_date(Local::now(), "%d/%m/%Y %H:%M:%S"),
-  )
+  log(Level::Warn, warn_type, message, None, 33)
 }
 
 pub fn warn_with_cause(warn_type: &str, message: &str, cause: &str) {
-  println!(
-    "\x1b[2m({d})\x1b[0m [\x1b[33m{warn_type}\x1b[0m]: \x1b[33m{message}\n                         Cause: {cause}\x1b[0m",
-    d = format_date(Local::now(), "%d/%m/%Y %H:%M:%S")
-  )
+  log(Level::Warn, warn_type, message, Some(("Cause", cause)), 33)
 }
 
 pub fn info(info_type: &str, message: &str) {
-  println!(
-    "\x1b[2m({d})\x1b[0m [\x1b[34m{info_type}\x1b[0m]: \x1b[34m{message}\x1b[0m",
-    d = format_date(Local::now(), "%d/%m/%Y %H:%M:%S")
-  )
+  log(Level::Info, info_type, message, None, 34)
 }
 
 pub fn info_with_detail(info_type: &str, message: &str, details: &str) {
-  println!(
-    "\x1b[2m({d})\x1b[0m [\x1b[34m{info_type}\x1b[0m]: \x1b[34m{message}\n                         Details: {details}\x1b[0m",
-    d = format_date(Local::now(), "%d/%m/%Y %H:%M:%S")
-  )
+  log(Level::Info, info_type, message, Some(("Details", details)), 34)
 }
 
 pub fn success(success_type: &str, message: &str) {
-  println!(
-    "\x1b[2m({d})\x1b[0m [\x1b[32m{success_type}\x1b[0m]: \x1b[32m{message}\x1b[0m",
-    d = format_date(Local::now(), "%d/%m/%Y %H:%M:%S"),
-  )
+  log(Level::Info, success_type, message, None, 32)
+}
+
+pub fn debug(debug_type: &str, message: &str) {
+  log(Level::Debug, debug_type, message, None, 90)
 }
 
 pub fn send(msg_type: &str, message: &str, type_color: i32) {
-  println!(
-    "\x1b[2m({d})\x1b[0m [\x1b[{type_color}m{msg_type}\x1b[0m]: {message}\x1b[0m",
-    d = format_date(Local::now(), "%d/%m/%Y %H:%M:%S")
-  )
+  log(Level::Info, msg_type, message, None, type_color)
 }
 
+/// Security messages are logged with the warnings, whatever the filter of their module
 pub fn security(info_type: &str, message: &str) {
-  println!(
-    "\x1b[2m({d})\x1b[0m [\x1b[36m{info_type}\x1b[0m]: \x1b[36m{message}\x1b[0m",
-    d = format_date(Local::now(), "%d/%m/%Y %H:%M:%S")
-  )
+  logging::log(Record { level: Level::Warn, module: info_type, message, detail: None, color: 36, security: true });
 }
 
 pub fn security_with_detail(info_type: &str, message: &str, details: &str) {
-  println!(
-    "\x1b[2m({d})\x1b[0m [\x1b[36m{info_type}\x1b[0m]: \x1b[36m{message}\n                         Details: {details}\x1b[0m",
-    d = format_date(Local::now(), "%d/%m/%Y %H:%M:%S")
-  )
+  logging::log(Record { level: Level::Warn, module: info_type, message, detail: Some(("Details", details)), color: 36, security: true });
 }
 
 /// "%d/%m/%Y %H:%M:%S"